@@ -0,0 +1,388 @@
+//! Caches git repositories and statuses between prompts when running as a daemon.
+//!
+//! The cache is only enabled by the daemon, otherwise every function passes straight through.
+//! Watched directories are checked for changes each time the cache is used so cached statuses are
+//! never out of date. Only the most recently used repositories are kept so the daemon doesn't grow
+//! with every directory it's asked about.
+
+use anyhow::Result;
+use git2::{Repository, Status};
+use once_cell::sync::OnceCell;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+static CACHE: OnceCell<Mutex<Cache>> = OnceCell::new();
+
+// Directories with idle repositories, and repositories with cached statuses
+const MAX_ENTRIES: usize = 16;
+// Idle repositories kept for each directory, one per component displayed in parallel
+const MAX_IDLE_REPOSITORIES: usize = 4;
+
+#[cfg(target_os = "linux")]
+pub fn enable() -> Result<()> {
+    let cache = Cache::new()?;
+    CACHE
+        .set(Mutex::new(cache))
+        .map_err(|_| anyhow::anyhow!("error: cache already enabled"))
+}
+
+// Returns an idle repository previously discovered from the directory
+pub fn take_repository(dir: &Path) -> Option<Repository> {
+    CACHE.get()?.lock().ok()?.take_repository(dir)
+}
+
+// Keeps the repository around for the next prompt drawn from the same directory
+pub fn return_repository(dir: PathBuf, repository: Repository) {
+    if let Some(Ok(mut cache)) = CACHE.get().map(Mutex::lock) {
+        cache.return_repository(dir, repository);
+    }
+}
+
+pub fn statuses<F>(repository: &Repository, compute: F) -> Result<Vec<Status>>
+where
+    F: FnOnce() -> Result<Vec<Status>>,
+{
+    let path = repository.path().to_owned();
+    let statuses = match cached_statuses(repository) {
+        Some(statuses) => statuses,
+        None => return compute(),
+    };
+
+    // The lock isn't held while computing so a status that never finishes only holds up the
+    // components asking for the same repository. They wait for the result instead of doing the
+    // work twice.
+    let result = statuses.get_or_try_init(compute).cloned();
+
+    // Failures aren't cached, the next component to ask tries again
+    if result.is_err() {
+        if let Some(Ok(mut cache)) = CACHE.get().map(Mutex::lock) {
+            let failed = (cache.statuses.get(&path))
+                .filter(|cached| Arc::ptr_eq(&cached.statuses, &statuses))
+                .is_some();
+            if failed {
+                cache.forget(&path);
+            }
+        }
+    }
+
+    result
+}
+
+// Returns the cached statuses of the repository, which are computed by whoever gets to them first,
+// or None when they can't be cached
+fn cached_statuses(repository: &Repository) -> Option<Arc<OnceCell<Vec<Status>>>> {
+    let mut cache = CACHE.get()?.lock().ok()?;
+
+    cache.invalidate();
+
+    let path = repository.path().to_owned();
+    if let Some(cached) = cache.statuses.get_mut(&path) {
+        cached.last_used = Instant::now();
+        return Some(cached.statuses.clone());
+    }
+
+    // The repository is watched before computing so changes made while computing invalidate the
+    // result. Without a watch on every directory changes could be missed so nothing is cached.
+    if !cache.watch(repository) {
+        return None;
+    }
+
+    if cache.statuses.len() >= MAX_ENTRIES {
+        if let Some(path) = least_recently_used(&cache.statuses, |cached| cached.last_used) {
+            cache.forget(&path);
+        }
+    }
+
+    let statuses = Arc::new(OnceCell::new());
+    cache.statuses.insert(
+        path,
+        Cached {
+            statuses: statuses.clone(),
+            last_used: Instant::now(),
+        },
+    );
+
+    Some(statuses)
+}
+
+fn least_recently_used<V, F>(entries: &HashMap<PathBuf, V>, last_used: F) -> Option<PathBuf>
+where
+    F: Fn(&V) -> Instant,
+{
+    entries
+        .iter()
+        .min_by_key(|(_, entry)| last_used(entry))
+        .map(|(path, _)| path.to_owned())
+}
+
+struct Idle {
+    repositories: Vec<Repository>,
+    last_used: Instant,
+}
+
+struct Cached {
+    statuses: Arc<OnceCell<Vec<Status>>>,
+    last_used: Instant,
+}
+
+struct Cache {
+    #[cfg(target_os = "linux")]
+    inotify: inotify::Inotify,
+    // Watched directories and the paths of the repositories they belong to, a directory can belong
+    // to more than one repository when repositories are nested. Only repositories with cached
+    // statuses are watched.
+    #[cfg(target_os = "linux")]
+    watches: HashMap<inotify::WatchDescriptor, Vec<PathBuf>>,
+    // Idle repositories by the directory they were discovered from
+    repositories: HashMap<PathBuf, Idle>,
+    // Statuses by repository path
+    statuses: HashMap<PathBuf, Cached>,
+}
+
+impl Cache {
+    // Idle repositories aren't watched so they're checked before being used instead, the
+    // repository could have been deleted or another one created within it
+    fn take_repository(&mut self, dir: &Path) -> Option<Repository> {
+        let idle = self.repositories.get_mut(dir)?;
+        idle.last_used = Instant::now();
+        let repository = idle.repositories.pop()?;
+
+        if is_discovered_from(dir, &repository) {
+            Some(repository)
+        } else {
+            self.repositories.remove(dir);
+            None
+        }
+    }
+
+    fn return_repository(&mut self, dir: PathBuf, repository: Repository) {
+        if !self.repositories.contains_key(&dir) && self.repositories.len() >= MAX_ENTRIES {
+            if let Some(dir) = least_recently_used(&self.repositories, |idle| idle.last_used) {
+                self.repositories.remove(&dir);
+            }
+        }
+
+        let idle = self.repositories.entry(dir).or_insert_with(|| Idle {
+            repositories: Vec::new(),
+            last_used: Instant::now(),
+        });
+        if idle.repositories.len() < MAX_IDLE_REPOSITORIES {
+            idle.repositories.push(repository);
+        }
+    }
+}
+
+// Whether discovering a repository from the directory would still find this one, which only takes
+// a few file checks rather than opening the repository again
+fn is_discovered_from(dir: &Path, repository: &Repository) -> bool {
+    if !repository.path().join("HEAD").exists() {
+        return false;
+    }
+
+    let root = repository.workdir().unwrap_or_else(|| repository.path());
+    let (dir, root) = match (dir.canonicalize(), root.canonicalize()) {
+        (Ok(dir), Ok(root)) => (dir, root),
+        _ => return false,
+    };
+    if !dir.starts_with(&root) {
+        return false;
+    }
+
+    // A repository between the directory and the root would be found first
+    !dir.ancestors()
+        .take_while(|ancestor| *ancestor != root)
+        .any(|ancestor| ancestor.join(".git").exists())
+}
+
+#[cfg(target_os = "linux")]
+impl Cache {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            inotify: inotify::Inotify::init()?,
+            watches: HashMap::new(),
+            repositories: HashMap::new(),
+            statuses: HashMap::new(),
+        })
+    }
+
+    // Forgets any repository that has changed since the cache was last used
+    fn invalidate(&mut self) {
+        use inotify::EventMask;
+
+        let mut buffer = [0; 4096];
+        loop {
+            let events = match self.inotify.read_events(&mut buffer) {
+                Ok(events) => events
+                    .map(|event| (event.wd, event.mask))
+                    .collect::<Vec<_>>(),
+                Err(_) => {
+                    self.forget_all();
+                    return;
+                }
+            };
+            if events.is_empty() {
+                return;
+            }
+
+            for (wd, mask) in events {
+                // Events were dropped so anything could have changed
+                if mask.contains(EventMask::Q_OVERFLOW) {
+                    self.forget_all();
+                    continue;
+                }
+
+                // The directory is gone, the parent directory's event forgets the repository
+                if mask.contains(EventMask::IGNORED) {
+                    self.watches.remove(&wd);
+                    continue;
+                }
+
+                for path in self.watches.get(&wd).cloned().unwrap_or_default() {
+                    self.forget(&path);
+                }
+            }
+        }
+    }
+
+    // Removes the statuses of the repository and stops watching it, it's watched again when its
+    // statuses are next computed
+    fn forget(&mut self, path: &Path) {
+        self.statuses.remove(path);
+
+        for paths in self.watches.values_mut() {
+            paths.retain(|watched| watched != path);
+        }
+        let unwatched = self
+            .watches
+            .iter()
+            .filter(|(_, paths)| paths.is_empty())
+            .map(|(wd, _)| wd.clone())
+            .collect::<Vec<_>>();
+        for wd in unwatched {
+            self.watches.remove(&wd);
+            let _ = self.inotify.rm_watch(wd);
+        }
+    }
+
+    fn forget_all(&mut self) {
+        self.statuses.clear();
+        for (wd, _) in self.watches.drain() {
+            let _ = self.inotify.rm_watch(wd);
+        }
+    }
+
+    // Watches the work tree and the parts of the git directory that affect the status, returning
+    // false if any directory couldn't be watched.
+    fn watch(&mut self, repository: &Repository) -> bool {
+        use inotify::WatchMask;
+
+        let mask = WatchMask::MODIFY
+            | WatchMask::ATTRIB
+            | WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVE
+            | WatchMask::ONLYDIR;
+
+        let path = repository.path().to_owned();
+        let mut dirs = vec![path.clone(), path.join("refs")];
+        if let Some(workdir) = repository.workdir() {
+            dirs.push(workdir.to_owned());
+        }
+
+        while let Some(dir) = dirs.pop() {
+            let wd = match self.inotify.add_watch(&dir, mask) {
+                Ok(wd) => wd,
+                Err(_) => {
+                    self.forget(&path);
+                    return false;
+                }
+            };
+            let paths = self.watches.entry(wd).or_default();
+            if !paths.contains(&path) {
+                paths.push(path.clone());
+            }
+
+            // The git directory is only watched at the top level, apart from refs
+            if dir == path {
+                continue;
+            }
+
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => {
+                    self.forget(&path);
+                    return false;
+                }
+            };
+            for entry in entries.flatten() {
+                let child = entry.path();
+                let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+                if is_dir && child != path && !is_ignored(repository, &child) {
+                    dirs.push(child);
+                }
+            }
+        }
+
+        true
+    }
+}
+
+// Ignored directories (e.g. target or node_modules) don't affect the status so aren't watched
+#[cfg(target_os = "linux")]
+fn is_ignored(repository: &Repository, path: &Path) -> bool {
+    repository
+        .workdir()
+        .and_then(|workdir| path.strip_prefix(workdir).ok())
+        .map(|path| repository.is_path_ignored(path).unwrap_or(false))
+        .unwrap_or(false)
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Cache {
+    fn invalidate(&mut self) {}
+
+    fn forget(&mut self, path: &Path) {
+        self.statuses.remove(path);
+    }
+
+    // Changes can't be watched for so statuses are never cached
+    fn watch(&mut self, _repository: &Repository) -> bool {
+        false
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_drops_idle_repositories_that_are_gone() {
+        let path = std::env::temp_dir().join(format!("aurora_prompt_cache_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let dir = path.join("sub");
+        std::fs::create_dir_all(&dir).unwrap();
+        Repository::init(&path).unwrap();
+
+        let mut cache = Cache::new().unwrap();
+        let repository = Repository::discover(&dir).unwrap();
+        cache.return_repository(dir.clone(), repository);
+        let repository = cache.take_repository(&dir).unwrap();
+        cache.return_repository(dir.clone(), repository);
+
+        // A repository created within it would be discovered instead
+        Repository::init(&dir).unwrap();
+        assert!(cache.take_repository(&dir).is_none());
+        assert!(!cache.repositories.contains_key(&dir));
+        std::fs::remove_dir_all(dir.join(".git")).unwrap();
+
+        let repository = Repository::discover(&dir).unwrap();
+        cache.return_repository(dir.clone(), repository);
+        std::fs::remove_dir_all(path.join(".git")).unwrap();
+        assert!(cache.take_repository(&dir).is_none());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
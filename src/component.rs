@@ -2,30 +2,56 @@ use anyhow::Result;
 
 use std::collections::HashMap;
 use std::fmt;
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crossterm::style::Color;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::glob;
 use crate::style::Style;
-use crate::token::{self, Condition, Token};
+use crate::token::{self, Condition, NumericComparison, StringComparison, Token};
 use crate::Context;
+use crate::Shell;
 
+mod cmd_duration;
 mod cwd;
 mod env;
+mod git_ahead_behind;
 mod git_branch;
 mod git_commit;
 mod git_stash;
+mod git_state;
 mod git_status;
 mod hostname;
 mod jobs;
 mod user;
 
+pub use cmd_duration::parse_duration;
+
 #[cfg(test)]
 mod tests;
 
+// Powerline arrow drawn between segments
+static SEGMENT_SEPARATOR: &str = "\u{e0b0}";
+
 #[derive(Debug, PartialEq)]
 enum Component {
     Static(String),
     Color(String),
     ColorReset(String),
     Computed(String),
+    Segment {
+        background: Color,
+        foreground: Option<Color>,
+        components: Vec<Option<Component>>,
+    },
+    Group(Vec<Option<Component>>),
+    // Replaced with the output of the job at this index once all the jobs have been displayed
+    Pending(usize),
 }
 
 impl fmt::Display for Component {
@@ -35,21 +61,108 @@ impl fmt::Display for Component {
             | Component::ColorReset(c)
             | Component::Static(c)
             | Component::Computed(c) => write!(f, "{}", c),
+            Component::Segment { components, .. } | Component::Group(components) => components
+                .iter()
+                .flatten()
+                .try_for_each(|c| write!(f, "{}", c)),
+            Component::Pending(_) => Ok(()),
         }
     }
 }
 
-pub fn components(tokens: Vec<Token>, mut context: &mut Context) -> Result<Vec<String>> {
-    let components = components_from_tokens(tokens, &mut context)?;
+pub fn components(tokens: Vec<Token>, context: &mut Context) -> Result<Vec<String>> {
+    let components = components_from_tokens(tokens, context, None)?;
+
+    Ok(render(components, &context.shell))
+}
+
+fn render(components: Vec<Option<Component>>, shell: &Shell) -> Vec<String> {
     let components = squash(components);
-    let components = components.iter().map(|c| c.to_string()).collect();
+    let components = render_segments(components, shell);
+    components.iter().map(|c| c.to_string()).collect()
+}
 
-    Ok(components)
+// How the components in a config were evaluated, used by the explain subcommand
+#[derive(Default)]
+pub struct Explanations {
+    pub components: Vec<Explanation>,
+    // Components are displayed in parallel so this can be less than the sum of their times
+    pub elapsed: Duration,
+}
+
+pub struct Explanation {
+    pub name: token::Component,
+    pub options: Vec<(String, String)>,
+    pub output: Option<String>,
+    pub elapsed: Duration,
+    pub squashed: bool,
+}
+
+pub fn explain(tokens: Vec<Token>, context: &mut Context) -> Result<Explanations> {
+    let mut explanations = Explanations::default();
+    let components = components_from_tokens(tokens, context, Some(&mut explanations))?;
+    let output = render(components, &context.shell).join("");
+
+    // Each component is followed by a marker, if the marker didn't make it into the output then
+    // the group the component was in has been squashed
+    for (i, explanation) in explanations.components.iter_mut().enumerate() {
+        explanation.squashed = !output.contains(&explanation_marker(i));
+    }
+
+    Ok(explanations)
 }
 
+// Static text never changes whether a group is kept, so markers don't affect squashing
+fn explanation_marker(index: usize) -> String {
+    format!("\0{}\0", index)
+}
+
+// Components are displayed in parallel once the tokens have been turned into components. Each
+// computed component starts out as Pending and is replaced once all the jobs have finished.
 fn components_from_tokens(
     tokens: Vec<Token>,
-    mut context: &mut Context,
+    context: &mut Context,
+    explanations: Option<&mut Explanations>,
+) -> Result<Vec<Option<Component>>> {
+    let mut jobs = Vec::new();
+    let components = plan_components(tokens, context, &mut jobs)?;
+
+    let explained = match explanations {
+        Some(_) => jobs
+            .iter()
+            .map(|job| (job.name, job.explained_options.clone()))
+            .collect(),
+        None => Vec::new(),
+    };
+    let start = Instant::now();
+    let outputs = display_jobs(jobs, context)?;
+    let elapsed = start.elapsed();
+
+    // Each component is followed by a marker, see explain
+    let components = resolve_components(components, &outputs, explanations.is_some());
+
+    if let Some(explanations) = explanations {
+        explanations.elapsed = elapsed;
+        explanations
+            .components
+            .extend(explained.into_iter().zip(outputs).map(
+                |((name, options), (output, elapsed))| Explanation {
+                    name,
+                    options,
+                    output,
+                    elapsed,
+                    squashed: false,
+                },
+            ));
+    }
+
+    Ok(components)
+}
+
+fn plan_components(
+    tokens: Vec<Token>,
+    context: &mut Context,
+    jobs: &mut Vec<Job>,
 ) -> Result<Vec<Option<Component>>> {
     let mut components = Vec::new();
 
@@ -59,52 +172,55 @@ fn components_from_tokens(
             Token::Color(color) => components.push(Some(Component::Color(
                 Style::from_color_token(&color, &context.shell).to_string(),
             ))),
+            // Background colors and attributes start groups just like colors
+            Token::BackgroundColor(color) => components.push(Some(Component::Color(
+                Style::BackgroundColor(&context.shell, color).to_string(),
+            ))),
+            Token::Attribute(attribute) => components.push(Some(Component::Color(
+                Style::Attribute(&context.shell, attribute).to_string(),
+            ))),
             Token::Reset => components.push(Some(Component::ColorReset(
                 Style::Reset(&context.shell).to_string(),
             ))),
-            Token::Component { name, mut options } => {
-                // Components should return Err when they encounter bad options, in other cases
-                // they should log their errors and return None, this way the prompt can always be
-                // rendered unless it's been incorrectly configured.
-                let c = match name {
-                    token::Component::GitBranch => git_branch::display(&context),
-                    token::Component::GitCommit => git_commit::display(&context),
-                    token::Component::GitStash => git_stash::display(&mut context),
-                    token::Component::GitStatus => git_status::display(&context)?,
-                    token::Component::Hostname => hostname::display(),
-                    token::Component::Jobs => jobs::display(context.backgrounded_jobs.as_deref()),
-                    token::Component::Cwd => cwd::display(&context, &mut options)?,
-                    token::Component::Env => env::display(&mut options)?,
-                    token::Component::User => user::display(),
-                };
-
-                // Components should use all the options they are given by removing them from the
-                // collection.
-                if !options.is_empty() {
-                    let options = options
-                        .iter()
-                        .map(|(k, v)| format!("{}={}", k, v))
-                        .collect::<Vec<_>>()
-                        .join(", ");
-
-                    return Err(anyhow::anyhow!("error: invalid options: {}", options));
-                }
-
-                components.push(c.map(Component::Computed));
+            Token::Component { name, options } => {
+                components.push(Some(Component::Pending(jobs.len())));
+                jobs.push(Job::new(name, options, context)?);
             }
+            Token::Segment {
+                background,
+                foreground,
+                tokens,
+            } => components.push(Some(Component::Segment {
+                background,
+                foreground,
+                components: plan_components(tokens, context, jobs)?,
+            })),
+            Token::Group(tokens) => components.push(Some(Component::Group(plan_components(
+                tokens, context, jobs,
+            )?))),
             Token::Conditional {
-                condition,
-                left,
-                right,
+                branches,
+                else_branch,
             } => {
-                let result = match condition {
-                    Condition::LastCommandStatus => context.last_command_status == 0,
-                    Condition::EnvironmentVariable(var_name) => std::env::var(var_name).is_ok(),
-                };
-                if result {
-                    components.append(&mut components_from_tokens(left, context)?);
-                } else if let Some(right) = right {
-                    components.append(&mut components_from_tokens(right, context)?);
+                // Only the first branch with a matching condition is used, the timeout is for the
+                // whole chain rather than each condition
+                let deadline = context.timeout.map(|timeout| Instant::now() + timeout);
+                let mut tokens = else_branch;
+                for (condition, branch) in branches {
+                    // Git conditions are as slow as git components, so like them they're left for
+                    // the async render along with the rest of the conditional
+                    if condition.is_git() && context.skip_slow_components {
+                        tokens = None;
+                        break;
+                    }
+                    if evaluate_with_timeout(&condition, context, deadline) {
+                        tokens = Some(branch);
+                        break;
+                    }
+                }
+
+                if let Some(tokens) = tokens {
+                    components.append(&mut plan_components(tokens, context, jobs)?);
                 }
             }
         };
@@ -113,6 +229,458 @@ fn components_from_tokens(
     Ok(components)
 }
 
+// Replaces pending components with the output of their job
+fn resolve_components(
+    components: Vec<Option<Component>>,
+    outputs: &[(Option<String>, Duration)],
+    explain: bool,
+) -> Vec<Option<Component>> {
+    let mut resolved = Vec::new();
+
+    for component in components {
+        match component {
+            Some(Component::Pending(i)) => {
+                resolved.push(outputs[i].0.clone().map(Component::Computed));
+                if explain {
+                    resolved.push(Some(Component::Static(explanation_marker(i))));
+                }
+            }
+            Some(Component::Segment {
+                background,
+                foreground,
+                components,
+            }) => resolved.push(Some(Component::Segment {
+                background,
+                foreground,
+                components: resolve_components(components, outputs, explain),
+            })),
+            Some(Component::Group(components)) => resolved.push(Some(Component::Group(
+                resolve_components(components, outputs, explain),
+            ))),
+            component => resolved.push(component),
+        }
+    }
+
+    resolved
+}
+
+// A component waiting to be displayed
+struct Job {
+    name: token::Component,
+    options: HashMap<String, String>,
+    explained_options: Vec<(String, String)>,
+    prefix: String,
+    suffix: String,
+    timeout: Option<Duration>,
+    placeholder: Option<String>,
+    slow: bool,
+    async_placeholder: Option<String>,
+}
+
+impl Job {
+    fn new(
+        name: token::Component,
+        mut options: HashMap<String, String>,
+        context: &Context,
+    ) -> Result<Self> {
+        let mut explained_options = options
+            .iter()
+            .map(|(k, v)| (k.to_owned(), v.to_owned()))
+            .collect::<Vec<_>>();
+        explained_options.sort();
+
+        // Prefix and suffix are available to every component and are only rendered when the
+        // component returns a value, unlike static text they don't need a color group.
+        let prefix = options.remove("prefix").unwrap_or_default();
+        let suffix = options.remove("suffix").unwrap_or_default();
+
+        // Slow components are replaced with their async placeholder when drawing the prompt before
+        // the async render has finished. Git components are slow unless told otherwise.
+        let slow = match options.remove("async").as_deref() {
+            Some("true") => true,
+            Some("false") => false,
+            Some(value) => return Err(anyhow::anyhow!("error: invalid async: {}", value)),
+            None => name.is_git(),
+        };
+        let async_placeholder = options.remove("async_placeholder");
+
+        // Components that take longer than their timeout are replaced with the placeholder, or
+        // None when there isn't one. The global timeout is only for slow components, the rest
+        // aren't worth a thread of their own.
+        let timeout = match options.remove("timeout") {
+            Some(timeout) => Some(parse_duration(&timeout)?),
+            None => context.timeout.filter(|_| slow),
+        };
+        let placeholder = options.remove("timeout_placeholder");
+
+        // Options are checked up front as the component might not be displayed here, e.g. when it
+        // times out or is skipped by the fast render
+        check_options(name, &options)?;
+
+        Ok(Self {
+            name,
+            options,
+            explained_options,
+            prefix,
+            suffix,
+            timeout,
+            placeholder,
+            slow,
+            async_placeholder,
+        })
+    }
+
+    // Slow components are left for the async render, their options have already been checked
+    fn is_skipped(&self, context: &Context) -> bool {
+        self.slow && context.skip_slow_components
+    }
+
+    // Returns the escaped output of the component and how long it took
+    fn display(mut self, context: &mut Context) -> Result<(Option<String>, Duration)> {
+        if self.is_skipped(context) {
+            let c = self.async_placeholder.take();
+            return Ok((
+                self.placeholder_output(c, &context.shell),
+                Duration::default(),
+            ));
+        }
+
+        let start = Instant::now();
+        let c = display(self.name, context, &mut self.options)?;
+
+        Ok((self.output(c, &context.shell), start.elapsed()))
+    }
+
+    // Computed values can contain anything (e.g. a branch named "$(rm -rf ~)") so they need to be
+    // escaped before being handed to the shell. The cwd component escapes its own output as it may
+    // contain style escape sequences.
+    fn output(&self, c: Option<String>, shell: &Shell) -> Option<String> {
+        let c = match self.name {
+            token::Component::Cwd => c,
+            _ => c.map(|c| shell.escape(&c)),
+        };
+        c.map(|c| format!("{}{}{}", self.prefix, c, self.suffix))
+    }
+
+    fn placeholder_output(&self, c: Option<String>, shell: &Shell) -> Option<String> {
+        c.map(|c| format!("{}{}{}", self.prefix, shell.escape(&c), self.suffix))
+    }
+}
+
+// A job displayed on a detached thread so it can be given up on once it times out
+struct TimedJob {
+    job: Job,
+    timeout: Duration,
+    start: Instant,
+    receiver: mpsc::Receiver<(Result<Option<String>>, Duration)>,
+}
+
+impl TimedJob {
+    fn spawn(mut job: Job, timeout: Duration, context: &Context) -> Self {
+        let name = job.name;
+        let mut options = std::mem::take(&mut job.options);
+        let receiver = spawn_detached(Detached::Component(name), context, move |context| {
+            let start = Instant::now();
+            let c = display(name, context, &mut options);
+            (c, start.elapsed())
+        });
+
+        Self {
+            job,
+            timeout,
+            start: Instant::now(),
+            receiver,
+        }
+    }
+
+    // Waits for whatever is left of the timeout, the component is replaced with its placeholder
+    // when it doesn't finish in time
+    fn wait(mut self, shell: &Shell) -> Result<(Option<String>, Duration)> {
+        let remaining = self.timeout.checked_sub(self.start.elapsed());
+        match self.receiver.recv_timeout(remaining.unwrap_or_default()) {
+            Ok((c, elapsed)) => Ok((self.job.output(c?, shell), elapsed)),
+            Err(_) => {
+                let c = self.job.placeholder.take();
+                Ok((self.job.placeholder_output(c, shell), self.start.elapsed()))
+            }
+        }
+    }
+}
+
+// How a job is being displayed by display_jobs
+enum PendingJob<'scope> {
+    Inline(Job),
+    Parallel(thread::ScopedJoinHandle<'scope, Result<(Option<String>, Duration)>>),
+    Timed(TimedJob),
+}
+
+// Slow components are displayed in parallel on threads of their own and components with a timeout
+// on detached threads, the rest are quick enough to be displayed on the current thread.
+//
+// The git repository can't be shared between threads so each thread opens its own, using the
+// repository discovered on the current thread rather than discovering it again.
+fn display_jobs(jobs: Vec<Job>, context: &mut Context) -> Result<Vec<(Option<String>, Duration)>> {
+    // A single slow component doesn't need a thread of its own
+    let parallel = jobs
+        .iter()
+        .filter(|job| job.slow && job.timeout.is_none() && !job.is_skipped(context))
+        .count()
+        > 1;
+
+    thread::scope(|scope| {
+        let pending = jobs
+            .into_iter()
+            .map(|job| {
+                if job.is_skipped(context) {
+                    return PendingJob::Inline(job);
+                }
+                if job.name.is_git() && (job.timeout.is_some() || parallel && job.slow) {
+                    context.git_repository();
+                }
+
+                match job.timeout {
+                    Some(timeout) => PendingJob::Timed(TimedJob::spawn(job, timeout, context)),
+                    None if parallel && job.slow => {
+                        let mut thread_context = context.detached();
+                        PendingJob::Parallel(scope.spawn(move || job.display(&mut thread_context)))
+                    }
+                    None => PendingJob::Inline(job),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        pending
+            .into_iter()
+            .map(|pending| match pending {
+                PendingJob::Inline(job) => job.display(context),
+                PendingJob::Parallel(handle) => handle
+                    .join()
+                    .unwrap_or_else(|err| std::panic::resume_unwind(err)),
+                PendingJob::Timed(job) => job.wait(&context.shell),
+            })
+            .collect()
+    })
+}
+
+// Components should use all the options they are given by removing them from the collection. The
+// options are removed from a copy without displaying the component, env and cmd_duration don't
+// have any side effects so they're displayed.
+fn check_options(name: token::Component, options: &HashMap<String, String>) -> Result<()> {
+    let mut options = options.clone();
+    match name {
+        token::Component::Cwd => {
+            cwd::check_options(&mut options)?;
+        }
+        token::Component::GitStatus => {
+            git_status::check_options(&mut options)?;
+        }
+        token::Component::CmdDuration => {
+            cmd_duration::display(None, &mut options)?;
+        }
+        token::Component::Env => {
+            env::check_options(&mut options)?;
+        }
+        _ => {}
+    }
+
+    if !options.is_empty() {
+        let mut options = options
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>();
+        options.sort();
+
+        return Err(anyhow::anyhow!(
+            "error: invalid options: {}",
+            options.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+// Components should return Err when they encounter bad options, in other cases they should log
+// their errors and return None, this way the prompt can always be rendered unless it's been
+// incorrectly configured.
+fn display(
+    name: token::Component,
+    context: &mut Context,
+    options: &mut HashMap<String, String>,
+) -> Result<Option<String>> {
+    let c = match name {
+        token::Component::GitAheadBehind => git_ahead_behind::display(context),
+        token::Component::GitBranch => git_branch::display(context),
+        token::Component::GitCommit => git_commit::display(context),
+        token::Component::GitStash => git_stash::display(context),
+        token::Component::GitState => git_state::display(context),
+        token::Component::GitStatus => git_status::display(context, options)?,
+        token::Component::Hostname => hostname::display(),
+        token::Component::Jobs => jobs::display(context.backgrounded_jobs.as_deref()),
+        token::Component::Cwd => cwd::display(context, options)?,
+        token::Component::CmdDuration => cmd_duration::display(context.command_duration, options)?,
+        token::Component::Env => env::display(context, options)?,
+        token::Component::User => user::display(context),
+    };
+
+    Ok(c)
+}
+
+// Git conditions can be as slow as git components so they share the global timeout, a condition
+// that doesn't finish before the deadline is false.
+fn evaluate_with_timeout(
+    condition: &Condition,
+    context: &Context,
+    deadline: Option<Instant>,
+) -> bool {
+    match deadline {
+        Some(deadline) if condition.is_git() => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::default() {
+                return false;
+            }
+
+            let condition = condition.clone();
+            spawn_detached(Detached::Condition, context, move |context| {
+                evaluate(&condition, context)
+            })
+            .recv_timeout(remaining)
+            .unwrap_or(false)
+        }
+        _ => evaluate(condition, context),
+    }
+}
+
+// Runs the function on another thread with its own context, sending the result to the returned
+// receiver. The thread is left running in the background if the receiver stops waiting as there's
+// no way to cancel it, it won't stop the prompt from being printed.
+//
+// Threads that timed out would pile up in the daemon if a component kept hanging, so once there
+// are too many of the same kind running the function isn't run and the receiver is disconnected
+// straight away.
+fn spawn_detached<T, F>(kind: Detached, context: &Context, f: F) -> mpsc::Receiver<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut Context) -> T + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+
+    let running = match DetachedThread::start(kind) {
+        Some(running) => running,
+        None => return receiver,
+    };
+
+    let mut thread_context = context.detached();
+    thread::spawn(move || {
+        let _running = running;
+        // The receiver is gone if the function timed out
+        let _ = sender.send(f(&mut thread_context));
+    });
+
+    receiver
+}
+
+// What a detached thread is running, a component that keeps hanging doesn't stop the others from
+// being given a thread
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+enum Detached {
+    Component(token::Component),
+    Condition,
+}
+
+static DETACHED_THREADS: Lazy<Mutex<HashMap<Detached, usize>>> = Lazy::new(Default::default);
+const MAX_DETACHED_THREADS: usize = 8;
+
+// Counts a running detached thread until it's dropped, even if the thread panics
+struct DetachedThread(Detached);
+
+impl DetachedThread {
+    fn start(kind: Detached) -> Option<Self> {
+        let mut threads = DETACHED_THREADS.lock().ok()?;
+        let running = threads.entry(kind).or_default();
+        if *running < MAX_DETACHED_THREADS {
+            *running += 1;
+            Some(DetachedThread(kind))
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for DetachedThread {
+    fn drop(&mut self) {
+        if let Ok(mut threads) = DETACHED_THREADS.lock() {
+            if let Some(running) = threads.get_mut(&self.0) {
+                *running -= 1;
+            }
+        }
+    }
+}
+
+fn evaluate(condition: &Condition, context: &Context) -> bool {
+    match condition {
+        Condition::LastCommandStatus => context.last_command_status == 0,
+        Condition::InGitRepo => context.git_repository().is_some(),
+        Condition::GitDirty => git_status::repo_statuses(context)
+            .ok()
+            .flatten()
+            .filter(|statuses| !statuses.is_empty())
+            .is_some(),
+        Condition::GitDetached => context
+            .git_repository()
+            .and_then(|r| r.head_detached().ok())
+            .unwrap_or(false),
+        Condition::GitBare => context.git_repository().filter(|r| r.is_bare()).is_some(),
+        Condition::EnvironmentVariable(var_name) => context.var(var_name).is_some(),
+        Condition::EnvironmentVariableComparison(var_name, comparison, value) => {
+            let var = context.var(var_name);
+            match comparison {
+                StringComparison::Equal => var.as_ref() == Some(value),
+                StringComparison::NotEqual => var.as_ref() != Some(value),
+                StringComparison::Matches => var
+                    .filter(|var| Regex::new(value).is_ok_and(|r| r.is_match(var)))
+                    .is_some(),
+            }
+        }
+        Condition::Status(comparison, value) => {
+            let status = context.last_command_status;
+            match comparison {
+                NumericComparison::Equal => status == *value,
+                NumericComparison::NotEqual => status != *value,
+                NumericComparison::Less => status < *value,
+                NumericComparison::LessOrEqual => status <= *value,
+                NumericComparison::Greater => status > *value,
+                NumericComparison::GreaterOrEqual => status >= *value,
+            }
+        }
+        Condition::CwdMatches(pattern) => glob::matches(
+            &expand_home_dir(pattern, context),
+            &context.current_dir().display().to_string(),
+        ),
+        Condition::FileExists { path, upward } => {
+            let path = PathBuf::from(expand_home_dir(path, context));
+            let current_dir = context.current_dir();
+            if *upward {
+                current_dir.ancestors().any(|dir| dir.join(&path).exists())
+            } else {
+                current_dir.join(&path).exists()
+            }
+        }
+        Condition::Not(condition) => !evaluate(condition, context),
+        Condition::And(left, right) => evaluate(left, context) && evaluate(right, context),
+        Condition::Or(left, right) => evaluate(left, context) || evaluate(right, context),
+    }
+}
+
+// Replaces a leading "~" with the home directory
+fn expand_home_dir(path: &str, context: &Context) -> String {
+    match (path.strip_prefix('~'), context.home_dir()) {
+        (Some(rest), Some(home_dir)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home_dir.display(), rest)
+        }
+        _ => path.to_owned(),
+    }
+}
+
 fn into_groups(components: Vec<Option<Component>>) -> Vec<Vec<Option<Component>>> {
     struct Groups {
         map: HashMap<usize, Vec<Option<Component>>>,
@@ -129,18 +697,16 @@ fn into_groups(components: Vec<Option<Component>>) -> Vec<Vec<Option<Component>>
             }
         }
 
-        fn current_group_empty(&self) -> bool {
+        // Consecutive styles (e.g. {bg_blue}{bold}) belong to the same group
+        fn current_group_only_styles(&self) -> bool {
             match self.map.get(&self.current_group_index) {
-                Some(g) => g.is_empty(),
+                Some(g) => g.iter().all(|c| matches!(c, Some(Component::Color(_)))),
                 None => true,
             }
         }
 
         fn add_to_current_group(&mut self, component: Option<Component>) {
-            let group = self
-                .map
-                .entry(self.current_group_index)
-                .or_insert_with(Vec::new);
+            let group = self.map.entry(self.current_group_index).or_default();
             group.push(component)
         }
 
@@ -160,7 +726,7 @@ fn into_groups(components: Vec<Option<Component>>) -> Vec<Vec<Option<Component>>
     for component in components {
         match component {
             Some(Component::Color(_)) => {
-                if groups.current_group_empty() {
+                if groups.current_group_only_styles() {
                     // If we're already in a new group
                     groups.add_to_current_group(component);
                 } else {
@@ -184,26 +750,130 @@ fn into_groups(components: Vec<Option<Component>>) -> Vec<Vec<Option<Component>>
 }
 
 fn squash(components: Vec<Option<Component>>) -> Vec<Component> {
-    into_groups(components)
+    into_groups(squash_segments(components))
         .into_iter()
-        .filter(|g| should_keep_group(&g))
+        .filter(|g| should_keep_group(g))
         .flatten()
-        .filter_map(|c| c)
+        .flatten()
+        // The contents of explicit groups that survived are inlined
+        .flat_map(|component| match component {
+            Component::Group(components) => components.into_iter().flatten().collect(),
+            component => vec![component],
+        })
+        .collect()
+}
+
+// Segments and explicit groups are squashed using the same rules as color groups. A squashed
+// segment or group is removed entirely so it doesn't affect the color group it's in.
+fn squash_segments(components: Vec<Option<Component>>) -> Vec<Option<Component>> {
+    components
+        .into_iter()
+        .filter_map(|component| match component {
+            Some(Component::Segment {
+                background,
+                foreground,
+                components,
+            }) => {
+                if should_keep_explicit_group(&components) {
+                    Some(Some(Component::Segment {
+                        background,
+                        foreground,
+                        components: squash(components).into_iter().map(Some).collect(),
+                    }))
+                } else {
+                    None
+                }
+            }
+            Some(Component::Group(components)) => {
+                if should_keep_explicit_group(&components) {
+                    Some(Some(Component::Group(
+                        squash(components).into_iter().map(Some).collect(),
+                    )))
+                } else {
+                    None
+                }
+            }
+            component => Some(component),
+        })
         .collect()
 }
 
+// Replaces the remaining segments with their colors and contents, drawing a separator between
+// adjacent segments and after the last segment in a run.
+fn render_segments(components: Vec<Component>, shell: &Shell) -> Vec<Component> {
+    let mut output = Vec::new();
+    let mut previous_background = None;
+
+    let end_segment = |background| {
+        Component::Static(format!(
+            "{}{}{}{}",
+            Style::Reset(shell),
+            Style::Color(shell, background),
+            SEGMENT_SEPARATOR,
+            Style::Reset(shell),
+        ))
+    };
+
+    for component in components {
+        match component {
+            Component::Segment {
+                background,
+                foreground,
+                components,
+            } => {
+                if let Some(previous_background) = previous_background {
+                    output.push(Component::Static(format!(
+                        "{}{}{}",
+                        Style::Color(shell, previous_background),
+                        Style::BackgroundColor(shell, background),
+                        SEGMENT_SEPARATOR,
+                    )));
+                }
+
+                output.push(Component::Color(format!(
+                    "{}{}",
+                    Style::BackgroundColor(shell, background),
+                    Style::Color(shell, foreground.unwrap_or(Color::Reset)),
+                )));
+                output.extend(components.into_iter().flatten());
+
+                previous_background = Some(background);
+            }
+            component => {
+                if let Some(previous_background) = previous_background.take() {
+                    output.push(end_segment(previous_background));
+                }
+                output.push(component);
+            }
+        }
+    }
+
+    if let Some(previous_background) = previous_background {
+        output.push(end_segment(previous_background));
+    }
+
+    output
+}
+
 fn should_keep_group(group: &[Option<Component>]) -> bool {
-    // Groups with just a Static and or Color/ColorReset should be kept:
+    // Groups with just a Static and or Color/ColorReset should be kept. Segments and explicit
+    // groups have already been checked by should_keep_explicit_group and removed by
+    // squash_segments when squashed, the ones left count as a Static or a computed value depending
+    // on what's inside them:
     //
     // {red}>{reset}
     //  ^   ^
     //  |   ` Static
     //  ` Color
-    let group_contains_only_static_or_color_or_color_reset = group.iter().all(|c| match c {
-        Some(Component::Color(_)) | Some(Component::ColorReset(_)) | Some(Component::Static(_)) => {
-            true
-        }
-        _ => false,
+    let group_contains_only_static_or_color_or_color_reset = group.iter().all(|c| {
+        matches!(
+            c,
+            Some(Component::Color(_))
+                | Some(Component::ColorReset(_))
+                | Some(Component::Static(_))
+                | Some(Component::Segment { .. })
+                | Some(Component::Group(_))
+        )
     });
 
     // If the group contains at least one computer value we want to keep it:
@@ -212,10 +882,47 @@ fn should_keep_group(group: &[Option<Component>]) -> bool {
     //      ^ ^
     //      | `None -- git_stash returned a None
     //      ` Static
-    let group_contains_a_computed_value = group.iter().any(|c| match c {
-        Some(Component::Computed(_)) => true,
-        _ => false,
-    });
+    let group_contains_a_computed_value = contains_a_computed_value(group);
 
     group_contains_only_static_or_color_or_color_reset || group_contains_a_computed_value
 }
+
+// Segments and explicit groups follow should_keep_group, except that nested segments and groups
+// count as the components inside them. A squashed one counts as a component that returned None and
+// a kept one counts as a computed value if it has one:
+//
+// {group}({group}{git_branch}{end}){end}
+//        ^        ^
+//        |        ` None -- git_branch returned a None
+//        ` Static
+fn should_keep_explicit_group(group: &[Option<Component>]) -> bool {
+    let mut group_contains_a_none = false;
+    let mut group_contains_a_computed_value = false;
+
+    for c in group {
+        match c {
+            Some(Component::Group(components)) | Some(Component::Segment { components, .. }) => {
+                if !should_keep_explicit_group(components) {
+                    group_contains_a_none = true;
+                } else if contains_a_computed_value(components) {
+                    group_contains_a_computed_value = true;
+                }
+            }
+            Some(Component::Computed(_)) => group_contains_a_computed_value = true,
+            None => group_contains_a_none = true,
+            _ => {}
+        }
+    }
+
+    group_contains_a_computed_value || !group_contains_a_none
+}
+
+fn contains_a_computed_value(group: &[Option<Component>]) -> bool {
+    group.iter().any(|c| match c {
+        Some(Component::Computed(_)) => true,
+        Some(Component::Group(components)) | Some(Component::Segment { components, .. }) => {
+            contains_a_computed_value(components)
+        }
+        _ => false,
+    })
+}
@@ -0,0 +1,109 @@
+use anyhow::Result;
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Displays how long the last command took to run.
+//
+// Options:
+//
+// min=2s
+//
+//      Durations shorter than min aren't displayed. Accepts "ms", "s", "m" and "h" units.
+//
+// Examples:
+//
+//      800ms   ->  800ms
+//      83s     ->  1m23s
+//      3605s   ->  1h0m5s
+pub fn display(
+    duration: Option<Duration>,
+    options: &mut HashMap<String, String>,
+) -> Result<Option<String>> {
+    let min = match options.remove("min") {
+        Some(min) => parse_duration(&min)?,
+        None => Duration::from_secs(2),
+    };
+
+    Ok(duration.filter(|d| *d >= min).map(format_duration))
+}
+
+pub fn parse_duration(input: &str) -> Result<Duration> {
+    let invalid = || anyhow::anyhow!("error: invalid duration: {}", input);
+
+    let split_at = input
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (value, unit) = input.split_at(split_at);
+    let value = value.parse::<u64>().map_err(|_| invalid())?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        "m" => Ok(Duration::from_secs(value * 60)),
+        "h" => Ok(Duration::from_secs(value * 60 * 60)),
+        _ => Err(invalid()),
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    if seconds == 0 {
+        return format!("{}ms", duration.as_millis());
+    }
+
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}h{}m{}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m{}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_durations() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("2s").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration("3m").unwrap(), Duration::from_secs(180));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("2d").is_err());
+    }
+
+    #[test]
+    fn it_formats_durations() {
+        assert_eq!(format_duration(Duration::from_millis(800)), "800ms");
+        assert_eq!(format_duration(Duration::from_millis(45_900)), "45s");
+        assert_eq!(format_duration(Duration::from_secs(83)), "1m23s");
+        assert_eq!(format_duration(Duration::from_secs(3605)), "1h0m5s");
+    }
+
+    #[test]
+    fn it_hides_durations_below_min() {
+        let mut options = HashMap::new();
+        assert_eq!(
+            display(Some(Duration::from_secs(1)), &mut options).unwrap(),
+            None
+        );
+
+        let mut options = HashMap::new();
+        options.insert("min".to_string(), "500ms".to_string());
+        assert_eq!(
+            display(Some(Duration::from_secs(1)), &mut options).unwrap(),
+            Some("1s".to_string())
+        );
+
+        let mut options = HashMap::new();
+        assert_eq!(display(None, &mut options).unwrap(), None);
+    }
+}
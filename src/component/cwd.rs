@@ -5,7 +5,7 @@ use crate::Shell;
 use anyhow::Result;
 
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 #[derive(Debug, PartialEq)]
 enum Style {
@@ -40,6 +40,11 @@ fn extract_options(options: &mut HashMap<String, String>) -> Result<Style> {
     }
 }
 
+// Removes the options used by display, returning an error if they are invalid
+pub fn check_options(options: &mut HashMap<String, String>) -> Result<()> {
+    extract_options(options).map(|_| ())
+}
+
 // Displays the current working directory.
 //
 // Options:
@@ -76,49 +81,49 @@ fn extract_options(options: &mut HashMap<String, String>) -> Result<Style> {
 // style=long
 //
 // Outputs the full path unmodified.
-pub fn display(
-    context: &Context,
-    mut options: &mut HashMap<String, String>,
-) -> Result<Option<String>> {
-    let style = extract_options(&mut options)?;
+pub fn display(context: &Context, options: &mut HashMap<String, String>) -> Result<Option<String>> {
+    let style = extract_options(options)?;
 
     let output = match style {
-        Style::Default => default(context.current_dir()),
+        Style::Default => context.shell.escape(&default(context)),
         Style::Short { underline_repo } => short(
-            &context.current_dir(),
-            &dirs::home_dir().unwrap_or_default(),
+            context.current_dir(),
+            &context.home_dir().unwrap_or_default(),
             context.git_repository().map(|r| r.path()),
             underline_repo,
             &context.shell,
         ),
-        Style::Long => long(context.current_dir()),
+        Style::Long => context.shell.escape(&long(context.current_dir())),
     };
 
     Ok(Some(output))
 }
 
-fn replace_home_dir(current_dir: &PathBuf, home_dir: &PathBuf) -> String {
+fn replace_home_dir(current_dir: &Path, home_dir: &Path) -> String {
     format!("{}", current_dir.display()).replacen(&format!("{}", home_dir.display()), "~", 1)
 }
 
-fn default(current_dir: &PathBuf) -> String {
-    replace_home_dir(current_dir, &dirs::home_dir().unwrap_or_default())
+fn default(context: &Context) -> String {
+    replace_home_dir(
+        context.current_dir(),
+        &context.home_dir().unwrap_or_default(),
+    )
 }
 
 fn short(
-    full_path: &PathBuf,
-    home_dir: &PathBuf,
+    full_path: &Path,
+    home_dir: &Path,
     git_path: Option<&Path>,
     underline_repo: bool,
     shell: &Shell,
 ) -> String {
     let git_path_length = git_path.map(|git_path| {
         let git_path = git_path.parent().unwrap(); // Remove ".git"
-        let git_path = replace_home_dir(&git_path.to_path_buf(), &home_dir);
+        let git_path = replace_home_dir(git_path, home_dir);
         git_path.split('/').count()
     });
 
-    let full_path = replace_home_dir(&full_path, &home_dir);
+    let full_path = replace_home_dir(full_path, home_dir);
     let full_path_length = full_path.split('/').count();
 
     full_path
@@ -129,13 +134,18 @@ fn short(
                 // Don't truncate the repository
                 if underline_repo {
                     use style::Style::{NoUnderline, Underlined};
-                    format!("{}{}{}", Underlined(shell), part, NoUnderline(shell))
+                    format!(
+                        "{}{}{}",
+                        Underlined(shell),
+                        shell.escape(part),
+                        NoUnderline(shell)
+                    )
                 } else {
-                    part.to_owned()
+                    shell.escape(part)
                 }
             } else if i == full_path_length - 1 {
                 // Or the final dir
-                part.to_owned()
+                shell.escape(part)
             } else {
                 // Truncate everything else
                 let p = part.get(0..1).unwrap_or("");
@@ -143,9 +153,9 @@ fn short(
                 //
                 // Eg. ~/.config/shell -> ~/.c/shell
                 if p == "." {
-                    part.get(0..2).unwrap_or(p).to_string()
+                    shell.escape(part.get(0..2).unwrap_or(p))
                 } else {
-                    p.to_string()
+                    shell.escape(p)
                 }
             }
         })
@@ -153,13 +163,14 @@ fn short(
         .join("/")
 }
 
-fn long(current_dir: &PathBuf) -> String {
+fn long(current_dir: &Path) -> String {
     format!("{}", current_dir.display())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::PathBuf;
 
     #[test]
     fn test_replace_home_dir() {
@@ -187,13 +198,13 @@ mod tests {
         let git_root = Path::new("/home/foo/axx/bxx/repo/.git");
 
         assert_eq!(
-            short(&current_dir, &home_dir, Some(&git_root), false, &Shell::Zsh),
+            short(&current_dir, &home_dir, Some(git_root), false, &Shell::Zsh),
             "~/a/b/repo/c/dxx".to_string()
         );
 
         let current_dir = PathBuf::from("/home/foo/axx/bxx/repo");
         assert_eq!(
-            short(&current_dir, &home_dir, Some(&git_root), false, &Shell::Zsh),
+            short(&current_dir, &home_dir, Some(git_root), false, &Shell::Zsh),
             "~/a/b/repo".to_string()
         );
     }
@@ -205,7 +216,7 @@ mod tests {
         let git_root = Path::new("/home/foo/axx/.git");
 
         assert_eq!(
-            short(&current_dir, &home_dir, Some(&git_root), false, &Shell::Zsh),
+            short(&current_dir, &home_dir, Some(git_root), false, &Shell::Zsh),
             "~/axx".to_string()
         );
     }
@@ -217,7 +228,7 @@ mod tests {
         let git_root = Path::new("/foo/bar/axx/.git");
 
         assert_eq!(
-            short(&current_dir, &home_dir, Some(&git_root), false, &Shell::Zsh),
+            short(&current_dir, &home_dir, Some(git_root), false, &Shell::Zsh),
             "/f/b/axx/b/c/dxx".to_string()
         );
     }
@@ -1,9 +1,61 @@
+use crate::Context;
+
 use anyhow::Result;
 use std::collections::HashMap;
 
-pub fn display(options: &mut HashMap<String, String>) -> Result<Option<String>> {
+fn extract_options(options: &mut HashMap<String, String>) -> Result<(String, Option<String>)> {
+    let default = options.remove("default");
+
     options.remove("name").map_or_else(
         || Err(anyhow::anyhow!("error: missing environment variable name")),
-        |name| Ok(std::env::var(name).ok()),
+        |name| Ok((name, default)),
     )
 }
+
+// Removes the options used by display, returning an error if they are invalid
+pub fn check_options(options: &mut HashMap<String, String>) -> Result<()> {
+    extract_options(options).map(|_| ())
+}
+
+// Displays the value of an environment variable.
+//
+// Options:
+//
+// name=HOME
+//
+//      The name of the environment variable, required.
+//
+// default=none
+//
+//      Displayed when the environment variable isn't set.
+pub fn display(context: &Context, options: &mut HashMap<String, String>) -> Result<Option<String>> {
+    let (name, default) = extract_options(options)?;
+
+    Ok(context.var(&name).or(default))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Shell;
+
+    #[test]
+    fn it_displays_a_default() {
+        let mut options = HashMap::new();
+        options.insert("name".to_string(), "AURORA_PROMPT_UNSET".to_string());
+        options.insert("default".to_string(), "none".to_string());
+
+        let context = Context::new(Shell::Zsh, 0, None, None);
+        assert_eq!(
+            display(&context, &mut options).unwrap(),
+            Some("none".to_string())
+        );
+        assert!(options.is_empty());
+    }
+
+    #[test]
+    fn it_requires_a_name() {
+        let mut options = HashMap::new();
+        assert!(check_options(&mut options).is_err());
+    }
+}
@@ -0,0 +1,38 @@
+use crate::Context;
+
+use git2::{Branch, Repository};
+
+// ⇡ Commits ahead of the upstream branch
+// ⇣ Commits behind the upstream branch
+pub fn display(context: &Context) -> Option<String> {
+    let repository = context.git_repository()?;
+    let (ahead, behind) = ahead_behind(repository)?;
+
+    let mut output = String::new();
+
+    if ahead > 0 {
+        output.push_str(&format!("⇡{}", ahead));
+    }
+
+    if behind > 0 {
+        output.push_str(&format!("⇣{}", behind));
+    }
+
+    if output.is_empty() {
+        None
+    } else {
+        Some(output)
+    }
+}
+
+fn ahead_behind(repository: &Repository) -> Option<(usize, usize)> {
+    let head = repository.head().ok()?;
+    if !head.is_branch() {
+        return None;
+    }
+
+    let local = head.target()?;
+    let upstream = Branch::wrap(head).upstream().ok()?.get().target()?;
+
+    repository.graph_ahead_behind(local, upstream).ok()
+}
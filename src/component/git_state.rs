@@ -0,0 +1,58 @@
+use crate::Context;
+
+use git2::{Repository, RepositoryState};
+
+use std::fs;
+use std::path::Path;
+
+// Displays the operation in progress, if any.
+//
+// Examples:
+//
+//      REBASE 3/7
+//      MERGING
+//      CHERRY-PICKING
+//      BISECTING
+//      REVERTING
+pub fn display(context: &Context) -> Option<String> {
+    let repository = context.git_repository()?;
+
+    let state = match repository.state() {
+        RepositoryState::Clean => return None,
+        RepositoryState::Merge => "MERGING",
+        RepositoryState::Revert | RepositoryState::RevertSequence => "REVERTING",
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => "CHERRY-PICKING",
+        RepositoryState::Bisect => "BISECTING",
+        RepositoryState::ApplyMailbox => "AM",
+        RepositoryState::ApplyMailboxOrRebase => "AM/REBASE",
+        RepositoryState::Rebase
+        | RepositoryState::RebaseInteractive
+        | RepositoryState::RebaseMerge => {
+            return match rebase_progress(repository) {
+                Some((step, total)) => Some(format!("REBASE {}/{}", step, total)),
+                None => Some("REBASE".to_string()),
+            }
+        }
+    };
+
+    Some(state.to_string())
+}
+
+// Interactive and merge based rebases store their progress in "rebase-merge", the older apply
+// based rebases use "rebase-apply".
+fn rebase_progress(repository: &Repository) -> Option<(usize, usize)> {
+    let path = repository.path();
+
+    read_progress(&path.join("rebase-merge"), "msgnum", "end")
+        .or_else(|| read_progress(&path.join("rebase-apply"), "next", "last"))
+}
+
+fn read_progress(path: &Path, step: &str, total: &str) -> Option<(usize, usize)> {
+    let read = |name| {
+        fs::read_to_string(path.join(name))
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+    };
+
+    Some((read(step)?, read(total)?))
+}
@@ -1,36 +1,103 @@
+use crate::cache;
 use crate::Context;
 use anyhow::Result;
-use git2::{Status, StatusEntry};
-
-// + New file added to the working tree
-// * File modified in the working tree
-// - File deleted from the working tree
-// ^ A change is staged
-pub fn display(context: &Context) -> Result<Option<String>> {
-    if let Some(repo_status) = repo_status(context)? {
-        let mut output = String::new();
-
-        if repo_status.is_wt_modified() {
-            output.push('*');
-        }
+use git2::{Status, StatusOptions};
 
-        if repo_status.is_wt_new() {
-            output.push('+');
-        }
+use std::collections::HashMap;
 
-        if repo_status.is_wt_deleted() {
-            output.push('-');
-        }
+#[derive(Debug, Default, PartialEq)]
+struct Counts {
+    staged: usize,
+    modified: usize,
+    untracked: usize,
+    deleted: usize,
+    renamed: usize,
+    conflicted: usize,
+}
 
-        if repo_status.intersects(
-            Status::INDEX_NEW
-                | Status::INDEX_MODIFIED
-                | Status::INDEX_DELETED
-                | Status::INDEX_RENAMED
-                | Status::INDEX_TYPECHANGE,
-        ) {
-            output.push('^');
-        }
+#[derive(Debug, PartialEq)]
+struct Symbols {
+    staged: String,
+    modified: String,
+    untracked: String,
+    deleted: String,
+    renamed: String,
+    conflicted: String,
+}
+
+fn extract_options(options: &mut HashMap<String, String>) -> (Option<String>, Symbols) {
+    let mut symbol = |name: &str, default: &str| {
+        options
+            .remove(&format!("{}_symbol", name))
+            .unwrap_or_else(|| default.to_owned())
+    };
+
+    let symbols = Symbols {
+        staged: symbol("staged", "^"),
+        modified: symbol("modified", "*"),
+        untracked: symbol("untracked", "+"),
+        deleted: symbol("deleted", "-"),
+        renamed: symbol("renamed", "»"),
+        conflicted: symbol("conflicted", "!"),
+    };
+
+    (options.remove("format"), symbols)
+}
+
+// Removes the options used by display, returning an error if the format is invalid
+pub fn check_options(options: &mut HashMap<String, String>) -> Result<()> {
+    if let (Some(format), _) = extract_options(options) {
+        parse_format(&format)?;
+    }
+    Ok(())
+}
+
+// Displays the status of the working tree.
+//
+// Options:
+//
+// <category>_symbol=
+//
+//      Overrides the symbol used for a category. The categories and their default symbols are:
+//
+//      staged      ^   A change is staged
+//      modified    *   File modified in the working tree
+//      untracked   +   New file added to the working tree
+//      deleted     -   File deleted from the working tree
+//      renamed     »   File renamed in the index or working tree
+//      conflicted  !   File has merge conflicts
+//
+// format=
+//
+//      By default a symbol is shown for each of the modified, untracked, deleted and staged
+//      categories that have changes, e.g. "*+^".
+//
+//      When a format is given the following placeholders are replaced with the count of files in
+//      that category followed by its symbol. Categories without changes are replaced with nothing
+//      along with the whitespace separating them from the next category, or from the previous
+//      one at the end of the format.
+//
+//      %s  staged
+//      %m  modified
+//      %u  untracked
+//      %d  deleted
+//      %r  renamed
+//      %c  conflicted
+//      %%  A literal "%"
+//
+//      Examples:
+//
+//      format=%m%u%s       ->  3*1+2^
+//      format="%m %u %s"   ->  3* 1+ 2^
+//      format=%c           ->  2!
+pub fn display(context: &Context, options: &mut HashMap<String, String>) -> Result<Option<String>> {
+    let (format, symbols) = extract_options(options);
+
+    if let Some(statuses) = repo_statuses(context)? {
+        let output = match format {
+            Some(format) => counted(&format, &count(&statuses), &symbols)?,
+            None => default(statuses.iter().fold(Status::empty(), add_status), &symbols),
+        };
 
         if !output.is_empty() {
             return Ok(Some(output));
@@ -39,19 +106,268 @@ pub fn display(context: &Context) -> Result<Option<String>> {
     Ok(None)
 }
 
-fn repo_status(context: &Context) -> Result<Option<Status>> {
-    if let Some(ref r) = context.git_repository() {
-        let status_options = None;
-        let statuses = r
-            .statuses(status_options)?
-            .iter()
-            .fold(Status::empty(), add_status);
+fn default(repo_status: Status, symbols: &Symbols) -> String {
+    let mut output = String::new();
+
+    if repo_status.is_wt_modified() {
+        output.push_str(&symbols.modified);
+    }
+
+    if repo_status.is_wt_new() {
+        output.push_str(&symbols.untracked);
+    }
+
+    if repo_status.is_wt_deleted() {
+        output.push_str(&symbols.deleted);
+    }
+
+    if repo_status.intersects(staged()) {
+        output.push_str(&symbols.staged);
+    }
+
+    output
+}
+
+#[derive(Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Staged,
+    Modified,
+    Untracked,
+    Deleted,
+    Renamed,
+    Conflicted,
+}
+
+fn parse_format(format: &str) -> Result<Vec<Part>> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = format.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            text.push(c);
+            continue;
+        }
+
+        let part = match chars.next() {
+            Some('s') => Part::Staged,
+            Some('m') => Part::Modified,
+            Some('u') => Part::Untracked,
+            Some('d') => Part::Deleted,
+            Some('r') => Part::Renamed,
+            Some('c') => Part::Conflicted,
+            Some('%') => {
+                text.push('%');
+                continue;
+            }
+            _ => return Err(anyhow::anyhow!("error: invalid format: {}", format)),
+        };
+        if !text.is_empty() {
+            parts.push(Part::Text(std::mem::take(&mut text)));
+        }
+        parts.push(part);
+    }
+
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+fn counted(format: &str, counts: &Counts, symbols: &Symbols) -> Result<String> {
+    let mut output = String::new();
+    // Set after a category without changes so the whitespace separating it is dropped
+    let mut after_empty = false;
+
+    for part in parse_format(format)? {
+        let (count, symbol) = match part {
+            Part::Text(text) if after_empty => {
+                output.push_str(text.trim_start());
+                after_empty = false;
+                continue;
+            }
+            Part::Text(text) => {
+                output.push_str(&text);
+                continue;
+            }
+            Part::Staged => (counts.staged, &symbols.staged),
+            Part::Modified => (counts.modified, &symbols.modified),
+            Part::Untracked => (counts.untracked, &symbols.untracked),
+            Part::Deleted => (counts.deleted, &symbols.deleted),
+            Part::Renamed => (counts.renamed, &symbols.renamed),
+            Part::Conflicted => (counts.conflicted, &symbols.conflicted),
+        };
+
+        after_empty = count == 0;
+        if !after_empty {
+            output.push_str(&format!("{}{}", count, symbol));
+        }
+    }
+
+    if after_empty {
+        output.truncate(output.trim_end().len());
+    }
+    Ok(output)
+}
+
+fn staged() -> Status {
+    Status::INDEX_NEW
+        | Status::INDEX_MODIFIED
+        | Status::INDEX_DELETED
+        | Status::INDEX_RENAMED
+        | Status::INDEX_TYPECHANGE
+}
+
+fn count(statuses: &[Status]) -> Counts {
+    let mut counts = Counts::default();
+
+    for status in statuses {
+        let increment = |count: &mut usize, flags: Status| {
+            if status.intersects(flags) {
+                *count += 1;
+            }
+        };
+
+        increment(&mut counts.staged, staged());
+        increment(&mut counts.modified, Status::WT_MODIFIED);
+        increment(&mut counts.untracked, Status::WT_NEW);
+        increment(&mut counts.deleted, Status::WT_DELETED);
+        increment(
+            &mut counts.renamed,
+            Status::INDEX_RENAMED | Status::WT_RENAMED,
+        );
+        increment(&mut counts.conflicted, Status::CONFLICTED);
+    }
+
+    counts
+}
+
+pub fn repo_statuses(context: &Context) -> Result<Option<Vec<Status>>> {
+    if let Some(r) = context.git_repository() {
+        let statuses = cache::statuses(r, || {
+            // Ignored files are included by default but never count towards the status, renames
+            // are only detected when asked for
+            let mut status_options = StatusOptions::new();
+            status_options
+                .include_untracked(true)
+                .recurse_untracked_dirs(true)
+                .include_ignored(false)
+                .renames_head_to_index(true)
+                .renames_index_to_workdir(true);
+            let statuses = r
+                .statuses(Some(&mut status_options))?
+                .iter()
+                .map(|entry| entry.status())
+                .collect();
+            Ok(statuses)
+        })?;
         return Ok(Some(statuses));
     }
     Ok(None)
 }
 
-fn add_status(mut s: Status, x: StatusEntry) -> Status {
-    s.insert(x.status());
+fn add_status(mut s: Status, x: &Status) -> Status {
+    s.insert(*x);
     s
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols() -> Symbols {
+        extract_options(&mut HashMap::new()).1
+    }
+
+    #[test]
+    fn it_displays_symbols_by_default() {
+        let status = Status::WT_MODIFIED | Status::WT_NEW | Status::INDEX_NEW;
+        assert_eq!(default(status, &symbols()), "*+^");
+    }
+
+    #[test]
+    fn it_counts_statuses() {
+        let statuses = vec![
+            Status::WT_MODIFIED,
+            Status::WT_MODIFIED | Status::INDEX_MODIFIED,
+            Status::WT_NEW,
+            Status::INDEX_RENAMED,
+            Status::CONFLICTED,
+        ];
+
+        assert_eq!(
+            count(&statuses),
+            Counts {
+                staged: 2,
+                modified: 2,
+                untracked: 1,
+                deleted: 0,
+                renamed: 1,
+                conflicted: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn it_formats_counts() {
+        let counts = Counts {
+            staged: 2,
+            modified: 3,
+            untracked: 1,
+            ..Counts::default()
+        };
+
+        assert_eq!(
+            counted("%m %u %s", &counts, &symbols()).unwrap(),
+            "3* 1+ 2^"
+        );
+        assert_eq!(
+            counted("%m %d %c %s", &counts, &symbols()).unwrap(),
+            "3* 2^"
+        );
+        assert_eq!(counted("%c", &counts, &symbols()).unwrap(), "");
+        assert_eq!(counted("%m%%", &counts, &symbols()).unwrap(), "3*%");
+        assert!(counted("%x", &counts, &symbols()).is_err());
+
+        // Only whitespace next to categories without changes is dropped
+        assert_eq!(
+            counted("%m  %u | %s", &counts, &symbols()).unwrap(),
+            "3*  1+ | 2^"
+        );
+        assert_eq!(counted(" %c %m %d", &counts, &symbols()).unwrap(), " 3*");
+        assert_eq!(
+            counted("(%m) %c (%s)", &counts, &symbols()).unwrap(),
+            "(3*) (2^)"
+        );
+    }
+
+    #[test]
+    fn it_checks_the_format() {
+        let mut options = HashMap::new();
+        options.insert("format".to_string(), "%m %x".to_string());
+
+        assert_eq!(
+            check_options(&mut options).unwrap_err().to_string(),
+            "error: invalid format: %m %x"
+        );
+        assert!(options.is_empty());
+    }
+
+    #[test]
+    fn it_uses_custom_symbols() {
+        let mut options = HashMap::new();
+        options.insert("modified_symbol".to_string(), "M".to_string());
+        options.insert("format".to_string(), "%m".to_string());
+
+        let (format, symbols) = extract_options(&mut options);
+        let counts = Counts {
+            modified: 3,
+            ..Counts::default()
+        };
+
+        assert!(options.is_empty());
+        assert_eq!(format, Some("%m".to_string()));
+        assert_eq!(counted("%m", &counts, &symbols).unwrap(), "3M");
+    }
+}
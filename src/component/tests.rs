@@ -135,13 +135,14 @@ fn test_components_from_tokens() {
     let mut options = HashMap::new();
     options.insert("foo".to_string(), "bar".to_string());
 
-    let mut context = Context::new(Shell::Zsh, 0, None);
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
     let result = components_from_tokens(
         vec![Token::Component {
             name: token::Component::Jobs,
             options,
         }],
         &mut context,
+        None,
     );
 
     assert_eq!(
@@ -257,3 +258,678 @@ fn test_into_groups_two_groups_no_color() {
         ]
     );
 }
+
+fn test_repository(name: &str, branch: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("aurora_prompt_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+
+    let repository = git2::Repository::init(&path).unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    let tree_id = repository.index().unwrap().write_tree().unwrap();
+    let tree = repository.find_tree(tree_id).unwrap();
+    let reference = format!("refs/heads/{}", branch);
+    repository
+        .commit(Some(&reference), &signature, &signature, "init", &tree, &[])
+        .unwrap();
+    repository.set_head(&reference).unwrap();
+
+    path
+}
+
+fn render(config: &str, context: &mut Context) -> String {
+    let tokens = crate::parser::parse(config).unwrap();
+    components(tokens, context).unwrap().join("")
+}
+
+#[test]
+fn test_escapes_hostile_branch_names() {
+    let path = test_repository("hostile_branch", "$(touch_pwned)`id`");
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(
+        render("{git_branch}", &mut context),
+        "\\$(touch_pwned)\\`id\\`"
+    );
+
+    let mut context = Context::new(Shell::Bash, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(
+        render("{git_branch}", &mut context),
+        "\\\\$(touch_pwned)\\\\`id\\\\`"
+    );
+
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_escapes_hostile_directory_names() {
+    let path = std::path::PathBuf::from("/tmp/$(rm -rf ~)/100%/a\\b");
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(
+        render("{cwd style=long}", &mut context),
+        "/tmp/\\$(rm -rf ~)/100%%/a\\\\b"
+    );
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render("{cwd style=short}", &mut context), "/t/\\$/1/a\\\\b");
+
+    let mut context = Context::new(Shell::Bash, 0, None, None);
+    context.current_dir.set(path).unwrap();
+    assert_eq!(
+        render("{cwd style=long}", &mut context),
+        "/tmp/\\\\$(rm -rf ~)/100%/a\\\\\\\\b"
+    );
+}
+
+#[test]
+fn test_git_ahead_behind() {
+    let path = test_repository("ahead_behind", "master");
+
+    let repository = git2::Repository::open(&path).unwrap();
+    let base = repository.head().unwrap().peel_to_commit().unwrap();
+    repository.branch("base", &base, false).unwrap();
+
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    let tree = base.tree().unwrap();
+    for message in &["one", "two"] {
+        let parent = repository.head().unwrap().peel_to_commit().unwrap();
+        repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &[&parent],
+            )
+            .unwrap();
+    }
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render("{git_ahead_behind}", &mut context), "");
+
+    repository
+        .find_branch("master", git2::BranchType::Local)
+        .unwrap()
+        .set_upstream(Some("base"))
+        .unwrap();
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render("{git_ahead_behind}", &mut context), "⇡2");
+
+    // The remote-tracking branch has a commit that isn't on master
+    repository
+        .remote("origin", &path.display().to_string())
+        .unwrap();
+    let head = repository.head().unwrap().peel_to_commit().unwrap();
+    repository
+        .commit(
+            Some("refs/remotes/origin/master"),
+            &signature,
+            &signature,
+            "three",
+            &tree,
+            &[&head],
+        )
+        .unwrap();
+    repository
+        .find_branch("master", git2::BranchType::Local)
+        .unwrap()
+        .set_upstream(Some("origin/master"))
+        .unwrap();
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render("{git_ahead_behind}", &mut context), "⇣1");
+
+    repository
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "four",
+            &tree,
+            &[&head],
+        )
+        .unwrap();
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render("{git_ahead_behind}", &mut context), "⇡1⇣1");
+
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_git_status_renames() {
+    let path = test_repository("status_renames", "master");
+
+    let repository = git2::Repository::open(&path).unwrap();
+    std::fs::write(path.join("a"), "one\ntwo\nthree\n").unwrap();
+    let mut index = repository.index().unwrap();
+    index.add_path(std::path::Path::new("a")).unwrap();
+    index.write().unwrap();
+    let tree = repository.find_tree(index.write_tree().unwrap()).unwrap();
+    let parent = repository.head().unwrap().peel_to_commit().unwrap();
+    let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+    repository
+        .commit(Some("HEAD"), &signature, &signature, "a", &tree, &[&parent])
+        .unwrap();
+
+    // git mv a b
+    std::fs::rename(path.join("a"), path.join("b")).unwrap();
+    index.remove_path(std::path::Path::new("a")).unwrap();
+    index.add_path(std::path::Path::new("b")).unwrap();
+    index.write().unwrap();
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(
+        render("{git_status format=\"%r %s\"}", &mut context),
+        "1» 1^"
+    );
+
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_git_status_format_outside_a_repository() {
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set("/tmp".into()).unwrap();
+
+    let tokens = crate::parser::parse("{git_status format=%x}").unwrap();
+    assert_eq!(
+        components(tokens, &mut context).unwrap_err().to_string(),
+        "error: invalid format: %x"
+    );
+}
+
+#[test]
+fn test_git_state() {
+    let path = test_repository("state", "master");
+    let git_path = path.join(".git");
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render("{git_state}", &mut context), "");
+
+    let head = std::fs::read_to_string(git_path.join("refs/heads/master")).unwrap();
+    std::fs::write(git_path.join("MERGE_HEAD"), &head).unwrap();
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render("{git_state}", &mut context), "MERGING");
+
+    std::fs::remove_file(git_path.join("MERGE_HEAD")).unwrap();
+    std::fs::create_dir(git_path.join("rebase-merge")).unwrap();
+    std::fs::write(git_path.join("rebase-merge/msgnum"), "3\n").unwrap();
+    std::fs::write(git_path.join("rebase-merge/end"), "7\n").unwrap();
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render("{git_state}", &mut context), "REBASE 3/7");
+
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_into_groups_consecutive_styles() {
+    let components = vec![
+        Some(Component::Color("green".to_string())),
+        Some(Component::Color("bold".to_string())),
+        None,
+        Some(Component::ColorReset("reset".to_string())),
+    ];
+
+    let groups = into_groups(components);
+
+    assert_eq!(
+        groups,
+        vec![vec![
+            Some(Component::Color("green".to_string())),
+            Some(Component::Color("bold".to_string())),
+            None,
+            Some(Component::ColorReset("reset".to_string())),
+        ]]
+    );
+}
+
+#[test]
+fn test_squash_background_colors_and_attributes() {
+    let mut context = Context::new(Shell::Fish, 0, None, None);
+    assert_eq!(
+        render(
+            "{bg_blue}a{env name=AURORA_PROMPT_UNSET}{bold}b{reset}",
+            &mut context
+        ),
+        "\u{1b}[1mb\u{1b}[0m"
+    );
+}
+
+#[test]
+fn test_segments() {
+    let mut context = Context::new(Shell::Fish, 0, None, None);
+    assert_eq!(
+        render(
+            "{segment bg=blue fg=black}a{end}\
+             {segment bg=green}{env name=AURORA_PROMPT_UNSET}{end}\
+             {segment bg=red}b{end}$",
+            &mut context
+        ),
+        // The green segment is squashed
+        "\u{1b}[48;5;12m\u{1b}[38;5;0ma\
+         \u{1b}[38;5;12m\u{1b}[48;5;9m\u{e0b0}\
+         \u{1b}[48;5;9m\u{1b}[39mb\
+         \u{1b}[0m\u{1b}[38;5;9m\u{e0b0}\u{1b}[0m\
+         $"
+    );
+}
+
+// HOME is always set and tests never change it, unlike variables set with `set_var` which race
+// with other tests reading the environment
+fn home(shell: Shell) -> String {
+    shell.escape(&std::env::var("HOME").unwrap())
+}
+
+#[test]
+fn test_groups() {
+    let config = "{group}a{env name=HOME}{end}\
+                  {group}b{group}c{env name=AURORA_PROMPT_UNSET}{end}{end}\
+                  {group}d{group}e{end}{end}\
+                  {segment bg=blue}f{env name=HOME}{group}g{env name=AURORA_PROMPT_UNSET}{end}{end}\
+                  {segment bg=red}{group}{env name=AURORA_PROMPT_UNSET}{end}{end}\
+                  {group}({segment bg=red}{env name=AURORA_PROMPT_UNSET}{end}){end}$";
+
+    let mut context = Context::new(Shell::Fish, 0, None, None);
+    assert_eq!(
+        render(config, &mut context),
+        // The nested group squashes its parent, groups of static text are kept. Segments follow
+        // the same rules, either can be squashed by a nested segment or group.
+        format!(
+            "a{home}de\
+             \u{1b}[48;5;12m\u{1b}[39mf{home}\
+             \u{1b}[0m\u{1b}[38;5;12m\u{e0b0}\u{1b}[0m\
+             $",
+            home = home(Shell::Fish)
+        )
+    );
+}
+
+#[test]
+fn test_kept_nested_groups() {
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set("/tmp".into()).unwrap();
+
+    // A kept nested group counts as its computed value, like the same components without it
+    let home = home(Shell::Zsh);
+    assert_eq!(
+        render(
+            "{group}({group}{env name=HOME}{end}{git_branch}){end}$",
+            &mut context
+        ),
+        format!("({})$", home)
+    );
+    assert_eq!(
+        render("{group}({env name=HOME}{git_branch}){end}$", &mut context),
+        format!("({})$", home)
+    );
+    assert_eq!(
+        render(
+            "{segment bg=blue}({group}{env name=HOME}{end}{git_branch}){end}$",
+            &mut context
+        ),
+        format!(
+            "%{{\u{1b}[48;5;12m%}}%{{\u{1b}[39m%}}({})\
+             %{{\u{1b}[0m%}}%{{\u{1b}[38;5;12m%}}\u{e0b0}%{{\u{1b}[0m%}}$",
+            home
+        )
+    );
+
+    // A nested group of static text doesn't
+    assert_eq!(
+        render("{group}({group}x{end}{git_branch}){end}$", &mut context),
+        "$"
+    );
+}
+
+#[test]
+fn test_prefix_and_suffix() {
+    let config = "{env name=HOME prefix=\"[\" suffix=\"] \"}\
+                  {env name=AURORA_PROMPT_UNSET prefix=< suffix=>}>";
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    assert_eq!(
+        render(config, &mut context),
+        format!("[{}] >", home(Shell::Zsh))
+    );
+}
+
+#[test]
+fn test_explain() {
+    let config = "{red}{env name=HOME}{reset}\
+                  {green}{env name=AURORA_PROMPT_UNSET}{env name=HOME}{reset}\
+                  {blue}{env name=AURORA_PROMPT_UNSET prefix=x}{reset}";
+    let tokens = crate::parser::parse(config).unwrap();
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    let explanations = explain(tokens, &mut context).unwrap();
+
+    let summary = explanations
+        .components
+        .iter()
+        .map(|e| (e.name.to_string(), e.output.as_deref(), e.squashed))
+        .collect::<Vec<_>>();
+    let home = home(Shell::Zsh);
+    assert_eq!(
+        summary,
+        vec![
+            ("env".to_string(), Some(home.as_str()), false),
+            ("env".to_string(), None, false),
+            ("env".to_string(), Some(home.as_str()), false),
+            ("env".to_string(), None, true),
+        ]
+    );
+    assert_eq!(
+        explanations.components[3].options,
+        vec![
+            ("name".to_string(), "AURORA_PROMPT_UNSET".to_string()),
+            ("prefix".to_string(), "x".to_string()),
+        ]
+    );
+
+    // The total is how long displaying took rather than the sum of every component
+    let longest = explanations.components.iter().map(|e| e.elapsed).max();
+    assert!(Some(explanations.elapsed) >= longest);
+}
+
+#[test]
+fn test_timeouts() {
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.timeout = Some(std::time::Duration::from_secs(60));
+    context.current_dir.set("/tmp".into()).unwrap();
+    assert_eq!(
+        render(
+            "{env name=HOME timeout=10s timeout_placeholder=…}{cwd}",
+            &mut context
+        ),
+        format!("{}/tmp", home(Shell::Zsh))
+    );
+
+    // The global timeout is only for slow components
+    context.timeout = Some(std::time::Duration::default());
+    assert_eq!(render("{cwd timeout_placeholder=…}", &mut context), "/tmp");
+
+    let tokens = crate::parser::parse("{env name=HOME timeout=soon}").unwrap();
+    assert_eq!(
+        components(tokens, &mut context).unwrap_err().to_string(),
+        "error: invalid duration: soon"
+    );
+
+    // Options are still checked when the component is displayed on another thread
+    let tokens = crate::parser::parse("{env name=HOME foo=bar}").unwrap();
+    assert_eq!(
+        components(tokens, &mut context).unwrap_err().to_string(),
+        "error: invalid options: foo=bar"
+    );
+
+    // Or doesn't finish in time
+    let tokens = crate::parser::parse("{cwd style=tiny timeout=0ms}").unwrap();
+    assert_eq!(
+        components(tokens, &mut context).unwrap_err().to_string(),
+        "error: invalid style: tiny"
+    );
+}
+
+#[test]
+fn test_parallel_components() {
+    let path = test_repository("parallel", "main");
+
+    // Git components are displayed on threads of their own, each opening the repository discovered
+    // on the current thread
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(
+        render(
+            "{if in_git_repo}{git_branch}{end}:{git_branch}{red}{git_stash}{reset}\
+             {group}({git_state}){end}{segment bg=blue}{git_branch}{end}",
+            &mut context
+        ),
+        "main:main%{\u{1b}[48;5;12m%}%{\u{1b}[39m%}main\
+         %{\u{1b}[0m%}%{\u{1b}[38;5;12m%}\u{e0b0}%{\u{1b}[0m%}"
+    );
+
+    // The first error is returned
+    let tokens = crate::parser::parse("{git_branch}{env name=HOME foo=bar}{jobs baz=qux}").unwrap();
+    assert_eq!(
+        components(tokens, &mut context).unwrap_err().to_string(),
+        "error: invalid options: foo=bar"
+    );
+
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_skip_slow_components() {
+    let path = test_repository("slow", "main");
+    let config = "{git_branch async_placeholder=… prefix=(}{git_commit}\
+                  {red}{git_branch async=false}{reset}\
+                  {env name=HOME async=true async_placeholder=%}\
+                  {env name=HOME}";
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    context.skip_slow_components = true;
+    assert_eq!(
+        render(config, &mut context),
+        format!(
+            "(…%{{\u{1b}[38;5;9m%}}main%{{\u{1b}[0m%}}%%{}",
+            home(Shell::Zsh)
+        )
+    );
+
+    // Conditionals are left for the async render from the first git condition, earlier conditions
+    // can still match
+    assert_eq!(
+        render(
+            "{if in_git_repo}r{else}e{end}{if $HOME}h{elif git_dirty}d{end}\
+             {if $AURORA_PROMPT_UNSET}u{elif not git_dirty}c{else}e{end}",
+            &mut context
+        ),
+        "h"
+    );
+
+    let tokens = crate::parser::parse("{git_branch async=maybe}").unwrap();
+    assert_eq!(
+        components(tokens, &mut context).unwrap_err().to_string(),
+        "error: invalid async: maybe"
+    );
+
+    // Options of skipped components are still checked
+    let tokens = crate::parser::parse("{git_branch foo=bar}").unwrap();
+    assert_eq!(
+        components(tokens, &mut context).unwrap_err().to_string(),
+        "error: invalid options: foo=bar"
+    );
+
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_boolean_conditions() {
+    let mut context = Context::new(Shell::Zsh, 1, None, None);
+    assert_eq!(
+        render(
+            "{if not last_command_status and ($HOME or $AURORA_PROMPT_UNSET)}a{else}b{end}",
+            &mut context
+        ),
+        "a"
+    );
+}
+
+#[test]
+fn test_elif_conditions() {
+    let config = "{if $AURORA_PROMPT_UNSET}a{elif $HOME}b{elif $PATH}c{else}d{end}";
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    assert_eq!(render(config, &mut context), "b");
+}
+
+#[test]
+fn test_comparison_conditions() {
+    let config = "{if $HOME =~ \"^/\" and status == 130}a{end}\
+                  {if $HOME =~ \"^[^/]\" or status < 1}b{end}\
+                  {if $AURORA_PROMPT_UNSET != \"prod\" and status >= 1}c{end}";
+
+    let mut context = Context::new(Shell::Zsh, 130, None, None);
+    assert_eq!(render(config, &mut context), "ac");
+}
+
+#[test]
+fn test_git_conditions() {
+    let config = "{if in_git_repo}r{end}\
+                  {if git_dirty}d{end}\
+                  {if git_detached}h{end}\
+                  {if git_bare}b{end}";
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context
+        .current_dir
+        .set(std::path::PathBuf::from("/"))
+        .unwrap();
+    assert_eq!(render(config, &mut context), "");
+
+    let path = test_repository("git_conditions", "master");
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render(config, &mut context), "r");
+
+    // Ignored files don't make the repository dirty
+    std::fs::write(path.join(".git/info/exclude"), "target/\n").unwrap();
+    std::fs::create_dir(path.join("target")).unwrap();
+    std::fs::write(path.join("target/x"), "").unwrap();
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render(config, &mut context), "r");
+
+    std::fs::write(path.join("new_file"), "").unwrap();
+    let repository = git2::Repository::open(&path).unwrap();
+    let head = repository.head().unwrap().target().unwrap();
+    repository.set_head_detached(head).unwrap();
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render(config, &mut context), "rdh");
+
+    // Git conditions are evaluated on another thread when there's a timeout
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    context.timeout = Some(std::time::Duration::from_secs(60));
+    assert_eq!(render(config, &mut context), "rdh");
+
+    // Git conditions aren't evaluated once the timeout for the chain has passed
+    context.timeout = Some(std::time::Duration::default());
+    assert_eq!(
+        render(
+            "{if in_git_repo}r{elif git_dirty}d{else}e{end}",
+            &mut context
+        ),
+        "e"
+    );
+
+    std::fs::remove_dir_all(path).unwrap();
+
+    let path = std::env::temp_dir().join(format!(
+        "aurora_prompt_git_conditions_bare_{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&path);
+    git2::Repository::init_bare(&path).unwrap();
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render(config, &mut context), "rb");
+
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_path_conditions() {
+    let path = std::env::temp_dir().join(format!(
+        "aurora_prompt_path_conditions_{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(path.join("a/b")).unwrap();
+    std::fs::write(path.join("Cargo.toml"), "").unwrap();
+
+    let config = format!(
+        "{{if cwd_matches \"{}/**\"}}m{{end}}\
+         {{if file_exists \"Cargo.toml\"}}f{{end}}\
+         {{if file_exists \"Cargo.toml\" upward=true}}u{{end}}",
+        path.display()
+    );
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.join("a/b")).unwrap();
+    assert_eq!(render(&config, &mut context), "mu");
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(path.clone()).unwrap();
+    assert_eq!(render(&config, &mut context), "mfu");
+
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.current_dir.set(std::env::temp_dir()).unwrap();
+    assert_eq!(render(&config, &mut context), "");
+
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_expand_home_dir() {
+    let home_dir = dirs::home_dir().unwrap();
+    let context = Context::new(Shell::Zsh, 0, None, None);
+
+    assert_eq!(
+        expand_home_dir("~", &context),
+        home_dir.display().to_string()
+    );
+    assert_eq!(
+        expand_home_dir("~/work/**", &context),
+        format!("{}/work/**", home_dir.display())
+    );
+    assert_eq!(expand_home_dir("~foo", &context), "~foo");
+    assert_eq!(expand_home_dir("/tmp", &context), "/tmp");
+}
+
+#[test]
+fn test_environment() {
+    // The daemon renders prompts with the environment of the shell rather than its own
+    let env = vec![
+        ("HOME", "/home/aurora"),
+        ("PWD", "/home/aurora/work"),
+        ("USER", "aurora"),
+        ("AURORA_PROMPT_SET", "set"),
+    ];
+    let mut context = Context::new(Shell::Zsh, 0, None, None);
+    context.env = Some(
+        env.into_iter()
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect(),
+    );
+
+    assert_eq!(
+        render(
+            "{cwd} {user} {env name=AURORA_PROMPT_SET} {env name=PATH default=none}\
+             {if cwd_matches \"~/work\" and $AURORA_PROMPT_SET == \"set\"} y{end}",
+            &mut context
+        ),
+        "~/work aurora set none y"
+    );
+}
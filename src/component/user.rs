@@ -1,3 +1,5 @@
-pub fn display() -> Option<String> {
-    std::env::var("USER").ok()
+use crate::Context;
+
+pub fn display(context: &Context) -> Option<String> {
+    context.var("USER")
 }
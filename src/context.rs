@@ -2,9 +2,12 @@ use anyhow::Context as AnyhowContext;
 use git2::Repository;
 use once_cell::sync::OnceCell;
 
+use std::collections::HashMap;
 use std::env;
 use std::path::PathBuf;
+use std::time::Duration;
 
+use crate::cache;
 use crate::Shell;
 
 pub struct Context {
@@ -12,7 +15,15 @@ pub struct Context {
     pub git_repository: OnceCell<Option<Repository>>,
     pub last_command_status: usize,
     pub backgrounded_jobs: Option<String>,
+    pub command_duration: Option<Duration>,
     pub shell: Shell,
+    // Used for components without a timeout option
+    pub timeout: Option<Duration>,
+    // Slow components are drawn later by the async zsh prompt
+    pub skip_slow_components: bool,
+    // The environment of the shell the prompt is for when it isn't this process's, e.g. when the
+    // daemon is rendering it
+    pub env: Option<HashMap<String, String>>,
 }
 
 impl Context {
@@ -20,28 +31,79 @@ impl Context {
         shell: Shell,
         last_command_status: usize,
         backgrounded_jobs: Option<String>,
+        command_duration: Option<Duration>,
     ) -> Self {
         Self {
             current_dir: OnceCell::new(),
             git_repository: OnceCell::new(),
             last_command_status,
             backgrounded_jobs,
+            command_duration,
             shell,
+            timeout: None,
+            skip_slow_components: false,
+            env: None,
         }
     }
 
+    // A new context with the same inputs, used when displaying components on other threads
+    pub fn detached(&self) -> Self {
+        let mut context = Self::new(
+            self.shell,
+            self.last_command_status,
+            self.backgrounded_jobs.clone(),
+            self.command_duration,
+        );
+        context.timeout = self.timeout;
+        context.skip_slow_components = self.skip_slow_components;
+        context.env = self.env.clone();
+        context
+            .current_dir
+            .set(self.current_dir().clone())
+            .expect("not yet initialized");
+
+        // Opening the repository this context already found is cheaper than discovering it again
+        if let Some(repository) = self.git_repository.get() {
+            let repository = repository.as_ref().and_then(|repository| {
+                cache::take_repository(self.current_dir()).or_else(|| {
+                    Repository::open(repository.workdir().unwrap_or_else(|| repository.path())).ok()
+                })
+            });
+            let _ = context.git_repository.set(repository);
+        }
+
+        context
+    }
+
     pub fn current_dir(&self) -> &PathBuf {
         self.current_dir.get_or_init(|| {
-            env::var("PWD")
+            self.var("PWD")
                 .map(PathBuf::from)
                 .with_context(|| "unable to get current dir")
                 .unwrap()
         })
     }
 
+    pub fn var(&self, name: &str) -> Option<String> {
+        match &self.env {
+            Some(env) => env.get(name).cloned(),
+            None => env::var(name).ok(),
+        }
+    }
+
+    pub fn home_dir(&self) -> Option<PathBuf> {
+        match &self.env {
+            Some(_) => self.var("HOME").map(PathBuf::from),
+            None => dirs::home_dir(),
+        }
+    }
+
     pub fn git_repository(&self) -> Option<&Repository> {
         self.git_repository
-            .get_or_init(|| Repository::discover(&self.current_dir()).ok())
+            .get_or_init(|| {
+                cache::take_repository(self.current_dir())
+                    .or_else(|| Repository::discover(self.current_dir()).ok())
+            })
             .as_ref()
     }
 
@@ -53,3 +115,15 @@ impl Context {
             .as_mut()
     }
 }
+
+// Repositories are kept for the next prompt when the cache is enabled
+impl Drop for Context {
+    fn drop(&mut self) {
+        let current_dir = self.current_dir.get().cloned();
+        let git_repository = std::mem::take(&mut self.git_repository).into_inner();
+
+        if let (Some(current_dir), Some(Some(repository))) = (current_dir, git_repository) {
+            cache::return_repository(current_dir, repository);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Serves prompts over a Unix socket so git repositories and statuses can be cached between
+//! prompts.
+//!
+//! "run" sends its arguments and environment to the daemon, which renders the prompt with the same
+//! environment and sends it back. When the daemon isn't running "run" renders the prompt itself.
+
+use anyhow::{Context as AnyhowContext, Result};
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::cache;
+
+// How long "run" waits for the daemon before rendering the prompt itself
+static REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, PartialEq)]
+struct Request {
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+}
+
+fn socket_path() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR").map(|dir| PathBuf::from(dir).join("aurora_prompt.sock"))
+}
+
+// Requests are handled one at a time. The handler is given the client's environment rather than
+// it being set for the whole process, components still running on other threads may be reading it.
+pub fn serve<F>(handler: F) -> Result<()>
+where
+    F: Fn(Vec<String>, HashMap<String, String>) -> Result<String>,
+{
+    let path = socket_path().ok_or_else(|| anyhow::anyhow!("error: XDG_RUNTIME_DIR is not set"))?;
+
+    // A socket left behind by a daemon that didn't shut down cleanly would stop us binding
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(anyhow::anyhow!("error: daemon already running"));
+        }
+        std::fs::remove_file(&path)
+            .with_context(|| format!("error: unable to remove socket: {}", path.display()))?;
+    }
+
+    cache::enable()?;
+    let listener = UnixListener::bind(&path)
+        .with_context(|| format!("error: unable to bind socket: {}", path.display()))?;
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(_) => continue,
+        };
+
+        let mut input = Vec::new();
+        let response = match stream.read_to_end(&mut input) {
+            Ok(_) => decode_request(&input)
+                .and_then(|request| handler(request.args, request.env.into_iter().collect())),
+            Err(err) => Err(err.into()),
+        };
+
+        // The client has given up waiting if this fails
+        let _ = stream.write_all(&encode_response(response));
+    }
+
+    Ok(())
+}
+
+// Returns None when the daemon isn't running or doesn't respond in time
+pub fn request(args: &[String]) -> Option<Result<String>> {
+    let mut stream = UnixStream::connect(socket_path()?).ok()?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT)).ok()?;
+
+    let env = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .collect();
+    let request = Request {
+        args: args.to_vec(),
+        env,
+    };
+
+    stream.write_all(&encode_request(&request)).ok()?;
+    stream.shutdown(Shutdown::Write).ok()?;
+
+    let mut output = Vec::new();
+    stream.read_to_end(&mut output).ok()?;
+
+    decode_response(&output)
+}
+
+// Requests are a list of null terminated fields, e.g. "arg=--shell=zsh\0env=HOME=/home/odin\0"
+fn encode_request(request: &Request) -> Vec<u8> {
+    let args = request.args.iter().map(|arg| format!("arg={}\0", arg));
+    let env = (request.env.iter()).map(|(name, value)| format!("env={}={}\0", name, value));
+
+    args.chain(env).collect::<String>().into_bytes()
+}
+
+fn decode_request(input: &[u8]) -> Result<Request> {
+    let invalid = || anyhow::anyhow!("error: invalid request");
+
+    let input = std::str::from_utf8(input).map_err(|_| invalid())?;
+    let mut request = Request {
+        args: Vec::new(),
+        env: Vec::new(),
+    };
+
+    for field in input.split_terminator('\0') {
+        match field.split_once('=').ok_or_else(invalid)? {
+            ("arg", arg) => request.args.push(arg.to_owned()),
+            ("env", env) => {
+                let (name, value) = env.split_once('=').ok_or_else(invalid)?;
+                request.env.push((name.to_owned(), value.to_owned()));
+            }
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(request)
+}
+
+// Responses are "ok" or "error" followed by a null byte and the prompt or error message
+fn encode_response(response: Result<String>) -> Vec<u8> {
+    match response {
+        Ok(prompt) => format!("ok\0{}", prompt),
+        Err(err) => format!("error\0{}", err),
+    }
+    .into_bytes()
+}
+
+fn decode_response(input: &[u8]) -> Option<Result<String>> {
+    let input = String::from_utf8(input.to_vec()).ok()?;
+
+    match input.split_once('\0')? {
+        ("ok", prompt) => Some(Ok(prompt.to_owned())),
+        ("error", message) => Some(Err(anyhow::anyhow!("{}", message))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_round_trips_requests() {
+        let request = Request {
+            args: vec!["run".to_string(), "--config={cwd} $ ".to_string()],
+            env: vec![("PWD".to_string(), "/tmp/a=b".to_string())],
+        };
+
+        assert_eq!(decode_request(&encode_request(&request)).unwrap(), request);
+        assert!(decode_request(b"foo=bar\0").is_err());
+    }
+
+    #[test]
+    fn it_round_trips_responses() {
+        // Zsh prompts contain a null byte separating the right prompt
+        let response = decode_response(&encode_response(Ok("left\0right".to_string())));
+        assert_eq!(response.unwrap().unwrap(), "left\0right");
+
+        let response = decode_response(&encode_response(Err(anyhow::anyhow!("error: oops"))));
+        assert_eq!(response.unwrap().unwrap_err().to_string(), "error: oops");
+
+        assert!(decode_response(b"nope").is_none());
+    }
+}
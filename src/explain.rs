@@ -0,0 +1,98 @@
+//! Formats the output of the explain subcommand.
+
+use crate::component::Explanations;
+
+static HEADINGS: [&str; 5] = ["COMPONENT", "OPTIONS", "OUTPUT", "TIME", "SQUASHED"];
+
+// Renders a row per evaluated component, followed by the total time spent evaluating them.
+// Outputs are debug formatted so whitespace and escape sequences are visible.
+pub fn table(explanations: &Explanations) -> String {
+    let mut rows = vec![HEADINGS.iter().map(|h| h.to_string()).collect::<Vec<_>>()];
+
+    for explanation in &explanations.components {
+        let options = explanation
+            .options
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let output = match &explanation.output {
+            Some(output) => format!("{:?}", output),
+            None => "None".to_owned(),
+        };
+        let squashed = if explanation.squashed { "yes" } else { "no" };
+
+        rows.push(vec![
+            explanation.name.to_string(),
+            options,
+            output,
+            format!("{:.2?}", explanation.elapsed),
+            squashed.to_owned(),
+        ]);
+    }
+
+    rows.push(vec![
+        "total".to_owned(),
+        String::new(),
+        String::new(),
+        format!("{:.2?}", explanations.elapsed),
+        String::new(),
+    ]);
+
+    let widths = (0..HEADINGS.len())
+        .map(|i| rows.iter().map(|r| r[i].chars().count()).max().unwrap_or(0))
+        .collect::<Vec<_>>();
+
+    rows.iter()
+        .map(|row| {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            format!("{}\n", line.trim_end())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Explanation;
+    use crate::token::Component;
+    use std::time::Duration;
+
+    #[test]
+    fn it_formats_a_table() {
+        let components = vec![
+            Explanation {
+                name: Component::GitBranch,
+                options: Vec::new(),
+                output: None,
+                elapsed: Duration::from_micros(1500),
+                squashed: true,
+            },
+            Explanation {
+                name: Component::Env,
+                options: vec![("name".to_owned(), "USER".to_owned())],
+                output: Some("twe4ked".to_owned()),
+                elapsed: Duration::from_micros(20),
+                squashed: false,
+            },
+        ];
+        // The components were displayed in parallel
+        let explanations = Explanations {
+            components,
+            elapsed: Duration::from_micros(1510),
+        };
+
+        assert_eq!(
+            table(&explanations),
+            "COMPONENT   OPTIONS    OUTPUT     TIME     SQUASHED\n\
+             git_branch             None       1.50ms   yes\n\
+             env         name=USER  \"twe4ked\"  20.00µs  no\n\
+             total                             1.51ms\n"
+        );
+    }
+}
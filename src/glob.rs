@@ -0,0 +1,70 @@
+//! Minimal glob matching for paths.
+//!
+//! - `*` matches any characters within a path segment
+//! - `?` matches a single character within a path segment
+//! - `**` matches any number of path segments, including none
+
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.split('/').collect::<Vec<_>>();
+    let path = path.split('/').collect::<Vec<_>>();
+
+    matches_segments(&pattern, &path)
+}
+
+fn matches_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| matches_segments(rest, &path[i..])),
+        Some((segment, rest)) => match path.split_first() {
+            Some((part, path)) => matches_segment(segment, part) && matches_segments(rest, path),
+            None => false,
+        },
+    }
+}
+
+fn matches_segment(pattern: &str, part: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let part = part.chars().collect::<Vec<_>>();
+
+    matches_chars(&pattern, &part)
+}
+
+fn matches_chars(pattern: &[char], part: &[char]) -> bool {
+    match pattern.split_first() {
+        None => part.is_empty(),
+        Some(('*', rest)) => (0..=part.len()).any(|i| matches_chars(rest, &part[i..])),
+        Some((c, rest)) => match part.split_first() {
+            Some((p, part)) => (*c == '?' || c == p) && matches_chars(rest, part),
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_literal_paths() {
+        assert!(matches("/home/foo", "/home/foo"));
+        assert!(!matches("/home/foo", "/home/foo/bar"));
+        assert!(!matches("/home/foo/bar", "/home/foo"));
+    }
+
+    #[test]
+    fn it_matches_wildcards() {
+        assert!(matches("/home/*/work", "/home/foo/work"));
+        assert!(matches("/home/f?o", "/home/foo"));
+        assert!(matches("/home/*.rs", "/home/main.rs"));
+        assert!(!matches("/home/*", "/home/foo/bar"));
+        assert!(!matches("/home/f?o", "/home/fo"));
+    }
+
+    #[test]
+    fn it_matches_double_wildcards() {
+        assert!(matches("/home/foo/work/**", "/home/foo/work"));
+        assert!(matches("/home/foo/work/**", "/home/foo/work/a/b/c"));
+        assert!(matches("/**/src", "/home/foo/src"));
+        assert!(!matches("/home/foo/work/**", "/home/foo/personal"));
+    }
+}
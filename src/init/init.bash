@@ -4,6 +4,11 @@ aurora_preexec() {
     # "aurora_preexec"
     local PREV_LAST_ARG=$1
 
+    # Record when the command started, EPOCHREALTIME requires Bash 5
+    if [[ -z "$__aurora_start" ]]; then
+        __aurora_start="${EPOCHREALTIME/[.,]/}"
+    fi
+
     : "$PREV_LAST_ARG"
 }
 
@@ -11,7 +16,12 @@ aurora_preexec() {
 aurora_precmd() {
     local __status=$?
     local __jobs="$(jobs -p | wc -l)"
-    PS1="$(__CMD__ run --config=__CONFIG__ --jobs="${__jobs:-__empty__}" --shell=bash --status="$__status")"
+    local __duration_ms
+    if [[ -n "$__aurora_start" ]]; then
+        __duration_ms=$(( (${EPOCHREALTIME/[.,]/} - __aurora_start) / 1000 ))
+    fi
+    unset __aurora_start
+    PS1="$(__CMD__ run __CONFIG__ __RIGHT_CONFIG__ __TIMEOUT__ --columns="${COLUMNS:-80}" --jobs="${__jobs:-__empty__}" --shell=bash --status="$__status" ${__duration_ms:+--duration-ms=$__duration_ms})"
 }
 
 # We want to avoid destroying an existing DEBUG hook. If we detect one, create
@@ -0,0 +1,12 @@
+function fish_prompt
+    set -l __status $status
+    set -l __duration_ms $CMD_DURATION
+    if test -z "$__duration_ms"
+        set __duration_ms 0
+    end
+    set -l __jobs (jobs -p | count)
+    if test "$__jobs" -eq 0
+        set __jobs __empty__
+    end
+    __CMD__ run __CONFIG__ __TIMEOUT__ --jobs="$__jobs" --shell=fish --status="$__status" --duration-ms="$__duration_ms"
+end
@@ -1,25 +1,77 @@
+mod cache;
 mod component;
 mod context;
+#[cfg(target_os = "linux")]
+pub mod daemon;
+mod explain;
+mod glob;
 mod parser;
+mod right_prompt;
 mod shell;
 mod style;
 mod token;
 
 use anyhow::Result;
 
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub use component::parse_duration;
 use context::Context;
 pub use shell::Shell;
 
-pub fn components(
-    config: &str,
+// A parsed config, parse errors are returned before anything is evaluated
+pub struct Config(Vec<token::Token>);
+
+impl FromStr for Config {
+    type Err = anyhow::Error;
+
+    fn from_str(config: &str) -> Result<Self> {
+        Ok(Config(parser::parse(config)?))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn prompt(
+    config: Config,
+    right_config: Option<Config>,
     shell: Shell,
     jobs: Option<String>,
     status: usize,
-) -> Result<Vec<String>> {
-    let tokens = parser::parse(config)?;
+    duration: Option<Duration>,
+    columns: usize,
+    timeout: Option<Duration>,
+    skip_slow_components: bool,
+    env: Option<HashMap<String, String>>,
+) -> Result<String> {
+    let mut context = Context::new(shell, status, jobs, duration);
+    context.timeout = timeout;
+    context.skip_slow_components = skip_slow_components;
+    context.env = env;
+    let left = component::components(config.0, &mut context)?.join("");
 
-    let mut context = Context::new(shell, status, jobs);
-    let components = component::components(tokens, &mut context)?;
+    match right_config {
+        Some(right_config) => {
+            let right = component::components(right_config.0, &mut context)?.join("");
+            Ok(right_prompt::join(&context.shell, left, right, columns))
+        }
+        None => Ok(left),
+    }
+}
+
+// Evaluates each component in the config, returning a table of their outputs and timings
+pub fn explain(
+    config: Config,
+    shell: Shell,
+    jobs: Option<String>,
+    status: usize,
+    duration: Option<Duration>,
+    timeout: Option<Duration>,
+) -> Result<String> {
+    let mut context = Context::new(shell, status, jobs, duration);
+    context.timeout = timeout;
+    let explanations = component::explain(config.0, &mut context)?;
 
-    Ok(components)
+    Ok(explain::table(&explanations))
 }
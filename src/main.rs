@@ -1,8 +1,12 @@
-use aurora_prompt::Shell;
+use aurora_prompt::{Config, Shell};
 
 use anyhow::{Context, Result};
 use clap::Clap;
 
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 static DEFAULT_CONFIG: &str = "{cwd} {git_branch} $ ";
 
 #[derive(Debug, Clap)]
@@ -17,6 +21,11 @@ enum SubCommand {
     Run(Run),
     /// Outputs init shell function. To be called from your dotfiles. This will in turn call "run"
     Init(Init),
+    /// Outputs how each component in the config was evaluated and how long it took
+    Explain(Explain),
+    /// Renders prompts for "run", caching git state between prompts
+    #[cfg(target_os = "linux")]
+    Daemon,
 }
 
 #[derive(Debug, Clap)]
@@ -25,18 +34,67 @@ pub struct Run {
     jobs: String,
     #[clap(long)]
     shell: Shell,
-    #[clap(long, default_value = DEFAULT_CONFIG)]
-    config: String,
+    /// Overrides the config file
+    #[clap(long)]
+    config: Option<String>,
+    /// Defaults to $XDG_CONFIG_HOME/aurora_prompt/config
+    #[clap(long, parse(from_os_str))]
+    config_file: Option<PathBuf>,
+    #[clap(long)]
+    right_config: Option<String>,
+    #[clap(long, default_value = "80")]
+    columns: usize,
+    #[clap(long)]
+    status: usize,
+    #[clap(long)]
+    duration_ms: Option<u64>,
+    /// Components taking longer than this are hidden, e.g. 500ms
+    #[clap(long, parse(try_from_str = aurora_prompt::parse_duration))]
+    timeout: Option<Duration>,
+    /// Replaces slow components with their async_placeholder and hides git conditionals
+    #[clap(long)]
+    fast: bool,
+}
+
+#[derive(Debug, Clap)]
+pub struct Explain {
+    #[clap(long)]
+    jobs: Option<String>,
+    #[clap(long, default_value = "zsh")]
+    shell: Shell,
+    /// Overrides the config file
     #[clap(long)]
+    config: Option<String>,
+    /// Defaults to $XDG_CONFIG_HOME/aurora_prompt/config
+    #[clap(long, parse(from_os_str))]
+    config_file: Option<PathBuf>,
+    #[clap(long, default_value = "0")]
     status: usize,
+    #[clap(long)]
+    duration_ms: Option<u64>,
+    /// Components taking longer than this are hidden, e.g. 500ms
+    #[clap(long, parse(try_from_str = aurora_prompt::parse_duration))]
+    timeout: Option<Duration>,
 }
 
 #[derive(Debug, Clap)]
 struct Init {
     #[clap(name = "shell")]
     shell: Shell,
-    #[clap(name = "config", default_value = DEFAULT_CONFIG)]
-    config: String,
+    /// Overrides the config file
+    #[clap(name = "config")]
+    config: Option<String>,
+    #[clap(name = "right_config")]
+    right_config: Option<String>,
+    /// Defaults to $XDG_CONFIG_HOME/aurora_prompt/config
+    #[clap(long, parse(from_os_str))]
+    config_file: Option<PathBuf>,
+    /// Components taking longer than this are hidden, e.g. 500ms
+    #[clap(long)]
+    timeout: Option<String>,
+    /// Draws slow components once they're ready, only supported for zsh
+    #[clap(long = "async")]
+    async_prompt: bool,
 }
 
 fn main() {
@@ -45,6 +103,9 @@ fn main() {
     match options.subcmd {
         SubCommand::Init(o) => init(o),
         SubCommand::Run(o) => run(o),
+        SubCommand::Explain(o) => explain(o),
+        #[cfg(target_os = "linux")]
+        SubCommand::Daemon => daemon(),
     }
     .unwrap_or_else(|err| {
         eprintln!("{}", err);
@@ -53,24 +114,164 @@ fn main() {
 }
 
 fn init(options: Init) -> Result<()> {
+    print!("{}", init_script(options)?);
+
+    Ok(())
+}
+
+fn init_script(options: Init) -> Result<String> {
+    let shell = options.shell;
     let script = match options.shell {
         Shell::Zsh => include_str!("init/init.zsh"),
         Shell::Bash => include_str!("init/init.bash"),
+        Shell::Fish => include_str!("init/init.fish"),
     };
 
     let path = std::env::current_exe().with_context(|| "could not return path to executable")?;
 
     let script = script.replace("__CMD__", &format!("\"{}\"", path.display()));
-    let script = script.replace("__CONFIG__", &format!("'{}'", options.config));
+    // Without a config the config file will be read by "run" every time the prompt is drawn
+    let config = match (options.config, options.config_file) {
+        (Some(config), _) => format!("--config={}", quote(&config, shell)),
+        (None, Some(config_file)) => {
+            // The prompt is drawn from whichever directory the shell is in
+            let config_file = std::fs::canonicalize(&config_file).with_context(|| {
+                format!(
+                    "error: unable to read config file: {}",
+                    config_file.display()
+                )
+            })?;
+            format!(
+                "--config-file={}",
+                quote(&format!("{}", config_file.display()), shell)
+            )
+        }
+        (None, None) => String::new(),
+    };
+    let script = script.replace("__CONFIG__", &config);
+    let script = match options.right_config {
+        Some(_) if matches!(options.shell, Shell::Fish) => {
+            return Err(anyhow::anyhow!(
+                "error: right prompt is not supported for fish"
+            ))
+        }
+        Some(right_config) => script.replace(
+            "__RIGHT_CONFIG__",
+            &format!("--right-config={}", quote(&right_config, shell)),
+        ),
+        None => script.replace("__RIGHT_CONFIG__", ""),
+    };
+    let script = match options.timeout {
+        Some(timeout) => {
+            aurora_prompt::parse_duration(&timeout)?;
+            script.replace(
+                "__TIMEOUT__",
+                &format!("--timeout={}", quote(&timeout, shell)),
+            )
+        }
+        None => script.replace("__TIMEOUT__", ""),
+    };
+    let script = match options.async_prompt {
+        true if !matches!(options.shell, Shell::Zsh) => {
+            return Err(anyhow::anyhow!(
+                "error: async prompts are only supported for zsh"
+            ))
+        }
+        true => script.replace("__ASYNC__", "1"),
+        false => script.replace("__ASYNC__", ""),
+    };
 
-    print!("{}", script);
+    Ok(script)
+}
 
-    Ok(())
+// Single quotes the input for the shell. Bash and Zsh don't have escapes within single quotes so
+// single quotes are closed, escaped, and reopened. Fish treats backslashes and single quotes
+// within single quotes as escapes.
+fn quote(input: &str, shell: Shell) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => format!("'{}'", input.replace('\'', "'\\''")),
+        Shell::Fish => format!("'{}'", input.replace('\\', "\\\\").replace('\'', "\\'")),
+    }
+}
+
+fn default_config_file(env: Option<&HashMap<String, String>>) -> Option<PathBuf> {
+    let var = |name| match env {
+        Some(env) => env.get(name).map(PathBuf::from),
+        None => std::env::var_os(name).map(PathBuf::from),
+    };
+    let home_dir = match env {
+        Some(_) => var("HOME"),
+        None => dirs::home_dir(),
+    };
+    let config_dir =
+        var("XDG_CONFIG_HOME").or_else(|| home_dir.map(|home_dir| home_dir.join(".config")))?;
+    let path = config_dir.join("aurora_prompt").join("config");
+
+    if path.exists() {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+fn read_config_file(path: &Path) -> Result<String> {
+    let config = std::fs::read_to_string(path)
+        .with_context(|| format!("error: unable to read config file: {}", path.display()))?;
+
+    // Editors usually add a newline to the end of the file which shouldn't end up in the prompt
+    Ok(config.trim_end_matches('\n').to_owned())
+}
+
+// Parses the config argument, falling back to the config file and then the default config
+fn load_config(
+    config: Option<String>,
+    config_file: Option<PathBuf>,
+    env: Option<&HashMap<String, String>>,
+) -> Result<Config> {
+    // The config argument takes precedence over the config file
+    let config_file = match config {
+        Some(_) => None,
+        None => config_file.or_else(|| default_config_file(env)),
+    };
+    match (config_file, config) {
+        // Parse errors from a config file are prefixed with the path so they can be found
+        (Some(path), _) => read_config_file(&path)?
+            .parse()
+            .map_err(|err| anyhow::anyhow!("{}: {}", path.display(), err)),
+        (None, Some(config)) => config.parse(),
+        (None, None) => DEFAULT_CONFIG.parse(),
+    }
 }
 
 fn run(options: Run) -> Result<()> {
+    // The daemon renders the prompt when it's running
+    #[cfg(target_os = "linux")]
+    {
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        if let Some(prompt) = aurora_prompt::daemon::request(&args) {
+            print!("{}", prompt?);
+            return Ok(());
+        }
+    }
+
+    print!("{}", render(options, None)?);
+
+    Ok(())
+}
+
+// Renders the prompt for a shell with the given environment, or this process's environment when
+// it's None
+fn render(options: Run, env: Option<HashMap<String, String>>) -> Result<String> {
     #[rustfmt::skip]
-    let Run { config, shell, jobs, status } = options;
+    let Run { config, config_file, right_config, columns, shell, jobs, status, duration_ms, timeout, fast } = options;
+
+    // Relative paths are from the shell's directory rather than the daemon's
+    let config_file = match (config_file, env.as_ref().and_then(|env| env.get("PWD"))) {
+        (Some(path), Some(dir)) if path.is_relative() => Some(Path::new(dir).join(path)),
+        (config_file, _) => config_file,
+    };
+    let config = load_config(config, config_file, env.as_ref())?;
+    let right_config = right_config.as_deref().map(str::parse).transpose()?;
 
     // https://github.com/clap-rs/clap/issues/1740
     let jobs = if jobs.is_empty() || jobs == "__empty__" {
@@ -79,9 +280,92 @@ fn run(options: Run) -> Result<()> {
         Some(jobs)
     };
 
-    for component in aurora_prompt::components(&config, shell, jobs, status)? {
-        print!("{}", component);
-    }
+    aurora_prompt::prompt(
+        config,
+        right_config,
+        shell,
+        jobs,
+        status,
+        duration_ms.map(Duration::from_millis),
+        columns,
+        timeout,
+        fast,
+        env,
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn daemon() -> Result<()> {
+    aurora_prompt::daemon::serve(|args, env| {
+        let args = std::iter::once("aurora_prompt".to_owned()).chain(args);
+        let options = Options::try_parse_from(args).map_err(|err| anyhow::anyhow!("{}", err))?;
+        match options.subcmd {
+            SubCommand::Run(o) => render(o, Some(env)),
+            _ => Err(anyhow::anyhow!("error: the daemon can only run prompts")),
+        }
+    })
+}
+
+fn explain(options: Explain) -> Result<()> {
+    #[rustfmt::skip]
+    let Explain { config, config_file, shell, jobs, status, duration_ms, timeout } = options;
+
+    let config = load_config(config, config_file, None)?;
+
+    let table = aurora_prompt::explain(
+        config,
+        shell,
+        jobs,
+        status,
+        duration_ms.map(Duration::from_millis),
+        timeout,
+    )?;
+    print!("{}", table);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(args: &[&str]) -> String {
+        let args = std::iter::once("init").chain(args.iter().copied());
+        init_script(Init::try_parse_from(args).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn init_bash() {
+        let script = script(&["bash", r"\'"]);
+        assert!(script.contains(r"--config='\'\'''"));
+
+        // The quoted config is passed through unchanged
+        let output = std::process::Command::new("bash")
+            .args(["-c", r"printf %s '\'\'''"])
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), r"\'");
+    }
+
+    #[test]
+    fn init_zsh() {
+        let script = script(&["zsh", r"\'", r"\'"]);
+        assert!(script.contains(r"--config='\'\''' --right-config='\'\'''"));
+    }
+
+    #[test]
+    fn init_fish() {
+        let script = script(&["fish", r"\'"]);
+        assert!(script.contains(r"--config='\\\''"));
+    }
+
+    #[test]
+    fn init_config_file() {
+        let script = script(&["bash", "--config-file=README.md"]);
+        let path = std::fs::canonicalize("README.md").unwrap();
+        assert!(script.contains(&format!("--config-file='{}'", path.display())));
+
+        let options = Init::try_parse_from(["init", "bash", "--config-file=missing"]).unwrap();
+        assert!(init_script(options).is_err());
+    }
+}
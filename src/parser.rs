@@ -1,13 +1,15 @@
-use crate::token::{Component, Condition, Token};
+use crate::token::{Component, Condition, NumericComparison, StringComparison, Token};
 use anyhow::Result;
-use crossterm::style::Color;
+use crossterm::style::{Attribute, Color};
 use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::character::complete::{alpha1, multispace0, none_of, one_of};
-use nom::combinator::{all_consuming, map, map_res, opt, recognize, verify};
-use nom::error::{convert_error, VerboseError};
+use nom::bytes::complete::{tag, take_while_m_n};
+use nom::character::complete::{
+    alpha1, alphanumeric1, anychar, char, digit1, multispace0, multispace1, none_of,
+};
+use nom::combinator::{all_consuming, cut, map, map_res, opt, recognize, verify};
+use nom::error::{context, convert_error, VerboseError};
 use nom::multi::{many0, many1};
-use nom::sequence::{pair, preceded, separated_pair, terminated, tuple};
+use nom::sequence::{delimited, pair, preceded, separated_pair, terminated, tuple};
 use once_cell::sync::Lazy;
 use std::collections::HashSet;
 use std::convert::TryFrom;
@@ -18,6 +20,7 @@ static RESERVED_KEYWORDS: Lazy<HashSet<&str>> = Lazy::new(|| {
     let mut s = HashSet::new();
     s.insert("end");
     s.insert("else");
+    s.insert("elif");
     s
 });
 
@@ -49,7 +52,89 @@ fn start_identifier_end(input: &str) -> IResult<&str, &str> {
 }
 
 fn color(input: &str) -> IResult<&str, Token> {
-    map(map_res(start_identifier_end, Color::try_from), Token::Color)(input)
+    map(
+        alt((named_color, hex_color, rgb_color, ansi_color)),
+        Token::Color,
+    )(input)
+}
+
+// {green}
+fn named_color(input: &str) -> IResult<&str, Color> {
+    map_res(start_identifier_end, Color::try_from)(input)
+}
+
+fn hex_byte(input: &str) -> IResult<&str, u8> {
+    map_res(take_while_m_n(2, 2, |c: char| c.is_ascii_hexdigit()), |s| {
+        u8::from_str_radix(s, 16)
+    })(input)
+}
+
+// {#ff8800}
+fn hex_color(input: &str) -> IResult<&str, Color> {
+    map(
+        delimited(
+            pair(start_tag, tag("#")),
+            tuple((hex_byte, hex_byte, hex_byte)),
+            end_tag,
+        ),
+        |(r, g, b)| Color::Rgb { r, g, b },
+    )(input)
+}
+
+fn decimal_byte(input: &str) -> IResult<&str, u8> {
+    map_res(digit1, str::parse)(input)
+}
+
+// {rgb 255 136 0}
+fn rgb_color(input: &str) -> IResult<&str, Color> {
+    map(
+        delimited(
+            pair(start_tag, tag("rgb")),
+            tuple((
+                preceded(multispace1, decimal_byte),
+                preceded(multispace1, decimal_byte),
+                preceded(multispace1, decimal_byte),
+            )),
+            end_tag,
+        ),
+        |(r, g, b)| Color::Rgb { r, g, b },
+    )(input)
+}
+
+// {color=208}
+fn ansi_color(input: &str) -> IResult<&str, Color> {
+    map(
+        delimited(pair(start_tag, tag("color=")), decimal_byte, end_tag),
+        Color::AnsiValue,
+    )(input)
+}
+
+// {bg_green}
+fn background_color(input: &str) -> IResult<&str, Token> {
+    let background_color = terminated(preceded(pair(start_tag, tag("bg_")), identifier), end_tag);
+    map(
+        map_res(background_color, Color::try_from),
+        Token::BackgroundColor,
+    )(input)
+}
+
+fn attribute_from_name(name: &str) -> Result<Attribute, ()> {
+    match name {
+        "bold" => Ok(Attribute::Bold),
+        "dim" => Ok(Attribute::Dim),
+        "italic" => Ok(Attribute::Italic),
+        "underline" => Ok(Attribute::Underlined),
+        "reverse" => Ok(Attribute::Reverse),
+        _ => Err(()),
+    }
+}
+
+// {bold}
+fn attribute(input: &str) -> IResult<&str, Token> {
+    map(
+        map_res(start_identifier_end, attribute_from_name),
+        Token::Attribute,
+    )(input)
 }
 
 fn reset(input: &str) -> IResult<&str, Token> {
@@ -61,27 +146,206 @@ fn if_start(input: &str) -> IResult<&str, ()> {
     map(pair(start_tag, tag("if")), drop)(input)
 }
 
+// POSIX names: letters, digits and underscores, not starting with a digit
 fn environment_variable_name(input: &str) -> IResult<&str, &str> {
     preceded(
         tag("$"),
-        recognize(many1(one_of("ABCDEFGHIJKLMNOPQRSTUVWXYZ_"))),
+        recognize(pair(
+            alt((alpha1, underscore)),
+            many0(alt((alphanumeric1, underscore))),
+        )),
     )(input)
 }
 
-fn if_condition(input: &str) -> IResult<&str, Condition> {
-    let basic_condition = map_res(identifier, Condition::try_from);
-    let environment_variable = map(environment_variable_name, |var_name: &str| {
-        Condition::EnvironmentVariable(var_name.to_owned())
+// Characters up to the closing quote, a backslash escapes the next character
+fn quoted_string_contents(quote: char) -> impl Fn(&str) -> IResult<&str, String> {
+    move |input| {
+        let escaped_char = preceded(char('\\'), anychar);
+        let unescaped_char = verify(anychar, |c| *c != '\\' && *c != quote);
+        let string = map(many0(alt((unescaped_char, escaped_char))), |chars| {
+            chars.into_iter().collect()
+        });
+
+        delimited(char(quote), string, char(quote))(input)
+    }
+}
+
+// "foo \"bar\"" or 'foo \'bar\''
+fn quoted_string(input: &str) -> IResult<&str, String> {
+    alt((quoted_string_contents('"'), quoted_string_contents('\'')))(input)
+}
+
+fn string_comparison(input: &str) -> IResult<&str, StringComparison> {
+    alt((
+        map(tag("=="), |_| StringComparison::Equal),
+        map(tag("!="), |_| StringComparison::NotEqual),
+        map(tag("=~"), |_| StringComparison::Matches),
+    ))(input)
+}
+
+fn numeric_comparison(input: &str) -> IResult<&str, NumericComparison> {
+    alt((
+        map(tag("=="), |_| NumericComparison::Equal),
+        map(tag("!="), |_| NumericComparison::NotEqual),
+        map(tag("<="), |_| NumericComparison::LessOrEqual),
+        map(tag(">="), |_| NumericComparison::GreaterOrEqual),
+        map(tag("<"), |_| NumericComparison::Less),
+        map(tag(">"), |_| NumericComparison::Greater),
+    ))(input)
+}
+
+// $TERM_PROGRAM or $TERM_PROGRAM == "vscode"
+fn environment_variable_condition(input: &str) -> IResult<&str, Condition> {
+    let comparison = pair(
+        delimited(multispace0, string_comparison, multispace0),
+        quoted_string,
+    );
+
+    // A regular expression that doesn't compile is reported when parsing rather than every time
+    // the prompt is drawn
+    let valid_comparison = verify(comparison, |(comparison, value): &(_, String)| {
+        *comparison != StringComparison::Matches || regex::Regex::new(value).is_ok()
     });
-    let condition = alt((basic_condition, environment_variable));
 
-    terminated(preceded(multispace0, condition), end_tag)(input)
+    map(
+        pair(environment_variable_name, opt(valid_comparison)),
+        |(name, comparison)| match comparison {
+            Some((comparison, value)) => {
+                Condition::EnvironmentVariableComparison(name.to_owned(), comparison, value)
+            }
+            None => Condition::EnvironmentVariable(name.to_owned()),
+        },
+    )(input)
+}
+
+// status >= 1
+fn status_condition(input: &str) -> IResult<&str, Condition> {
+    map(
+        preceded(
+            tag("status"),
+            pair(
+                delimited(multispace0, numeric_comparison, multispace0),
+                map_res(digit1, str::parse),
+            ),
+        ),
+        |(comparison, value)| Condition::Status(comparison, value),
+    )(input)
+}
+
+// cwd_matches "~/work/**"
+fn cwd_matches_condition(input: &str) -> IResult<&str, Condition> {
+    map(
+        preceded(pair(tag("cwd_matches"), multispace1), quoted_string),
+        Condition::CwdMatches,
+    )(input)
+}
+
+fn boolean(input: &str) -> IResult<&str, bool> {
+    alt((map(tag("true"), |_| true), map(tag("false"), |_| false)))(input)
+}
+
+// file_exists "Cargo.toml" upward=true
+fn file_exists_condition(input: &str) -> IResult<&str, Condition> {
+    let upward = preceded(pair(multispace1, tag("upward=")), boolean);
+
+    map(
+        preceded(
+            pair(tag("file_exists"), multispace1),
+            pair(quoted_string, opt(upward)),
+        ),
+        |(path, upward)| Condition::FileExists {
+            path,
+            upward: upward.unwrap_or(false),
+        },
+    )(input)
+}
+
+fn basic_condition(input: &str) -> IResult<&str, Condition> {
+    let basic_condition = map_res(identifier, Condition::try_from);
+
+    alt((
+        basic_condition,
+        status_condition,
+        cwd_matches_condition,
+        file_exists_condition,
+        environment_variable_condition,
+    ))(input)
+}
+
+// not condition
+fn not_condition(input: &str) -> IResult<&str, Condition> {
+    map(
+        preceded(pair(tag("not"), multispace0), unary_condition),
+        |condition| Condition::Not(Box::new(condition)),
+    )(input)
+}
+
+// (condition)
+fn parenthesized_condition(input: &str) -> IResult<&str, Condition> {
+    delimited(
+        pair(tag("("), multispace0),
+        condition,
+        pair(multispace0, tag(")")),
+    )(input)
+}
+
+fn unary_condition(input: &str) -> IResult<&str, Condition> {
+    alt((basic_condition, not_condition, parenthesized_condition))(input)
+}
+
+fn binary_operator<'a>(operator: &'static str) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
+    delimited(multispace1, tag(operator), multispace0)
+}
+
+// condition and condition
+fn and_condition(input: &str) -> IResult<&str, Condition> {
+    map(
+        pair(
+            unary_condition,
+            many0(preceded(binary_operator("and"), unary_condition)),
+        ),
+        |(first, rest)| {
+            rest.into_iter().fold(first, |left, right| {
+                Condition::And(Box::new(left), Box::new(right))
+            })
+        },
+    )(input)
+}
+
+// condition or condition, "and" binds tighter than "or"
+fn condition(input: &str) -> IResult<&str, Condition> {
+    map(
+        pair(
+            and_condition,
+            many0(preceded(binary_operator("or"), and_condition)),
+        ),
+        |(first, rest)| {
+            rest.into_iter().fold(first, |left, right| {
+                Condition::Or(Box::new(left), Box::new(right))
+            })
+        },
+    )(input)
+}
+
+fn if_condition(input: &str) -> IResult<&str, Condition> {
+    terminated(
+        preceded(multispace0, context("condition", condition)),
+        end_tag,
+    )(input)
 }
 
 fn end(input: &str) -> IResult<&str, ()> {
     map(tag("{end}"), drop)(input)
 }
 
+fn elif_start(input: &str) -> IResult<&str, ()> {
+    map(pair(start_tag, tag("elif")), drop)(input)
+}
+
+fn elif_branch(input: &str) -> IResult<&str, (Condition, Vec<Token>)> {
+    pair(preceded(elif_start, cut(if_condition)), tokens)(input)
+}
+
 fn if_else_tag(input: &str) -> IResult<&str, ()> {
     map(tag("{else}"), drop)(input)
 }
@@ -94,9 +358,15 @@ fn conditional(input: &str) -> IResult<&str, Token> {
     map(
         tuple((
             // {if condition}
-            preceded(if_start, if_condition),
+            //
+            // Once "{if" has been seen the condition must be valid, this stops the parser from
+            // backtracking so errors point at the condition.
+            preceded(if_start, cut(if_condition)),
             // foo bar baz
             tokens,
+            // {elif condition}
+            // foo bar baz
+            many0(elif_branch),
             terminated(
                 // {else}
                 // foo bar baz
@@ -105,27 +375,98 @@ fn conditional(input: &str) -> IResult<&str, Token> {
                 end,
             ),
         )),
-        |(condition, left, right)| Token::Conditional {
-            condition,
-            left,
-            right,
+        |(condition, tokens, elif_branches, else_branch)| {
+            let mut branches = vec![(condition, tokens)];
+            branches.extend(elif_branches);
+            Token::Conditional {
+                branches,
+                else_branch,
+            }
         },
     )(input)
 }
 
+// Colors given as options, e.g. bg=blue or bg=#ff8800
+fn option_color(value: &str) -> Result<Color, ()> {
+    let hex = preceded(tag("#"), tuple((hex_byte, hex_byte, hex_byte)));
+    match all_consuming(hex)(value) {
+        Ok((_, (r, g, b))) => Ok(Color::Rgb { r, g, b }),
+        Err(_) => Color::try_from(value),
+    }
+}
+
+fn segment_colors(options: Vec<(&str, String)>) -> Result<(Color, Option<Color>), ()> {
+    let mut background = None;
+    let mut foreground = None;
+
+    for (key, value) in options {
+        match key {
+            "bg" => background = Some(option_color(&value)?),
+            "fg" => foreground = Some(option_color(&value)?),
+            _ => return Err(()),
+        }
+    }
+
+    Ok((background.ok_or(())?, foreground))
+}
+
+fn segment_start(input: &str) -> IResult<&str, (Color, Option<Color>)> {
+    map_res(
+        delimited(pair(start_tag, tag("segment")), key_values, end_tag),
+        segment_colors,
+    )(input)
+}
+
+fn segment(input: &str) -> IResult<&str, Token> {
+    map(
+        tuple((
+            // {segment bg=blue fg=black}
+            segment_start,
+            // foo bar baz
+            terminated(
+                tokens, // {end}
+                end,
+            ),
+        )),
+        |((background, foreground), tokens)| Token::Segment {
+            background,
+            foreground,
+            tokens,
+        },
+    )(input)
+}
+
+fn group(input: &str) -> IResult<&str, Token> {
+    map(
+        delimited(
+            // {group}
+            tag("{group}"),
+            // foo bar baz
+            tokens,
+            // {end}
+            end,
+        ),
+        Token::Group,
+    )(input)
+}
+
 fn key(input: &str) -> IResult<&str, &str> {
     preceded(multispace0, alpha_underscore)(input)
 }
 
-fn value(input: &str) -> IResult<&str, &str> {
-    recognize(many1(none_of("} ")))(input)
+fn unquoted_value(input: &str) -> IResult<&str, String> {
+    map(recognize(many1(none_of("} "))), str::to_owned)(input)
 }
 
-fn key_value(input: &str) -> IResult<&str, (&str, &str)> {
+fn value(input: &str) -> IResult<&str, String> {
+    alt((quoted_string, unquoted_value))(input)
+}
+
+fn key_value(input: &str) -> IResult<&str, (&str, String)> {
     separated_pair(key, tag("="), value)(input)
 }
 
-fn key_values(input: &str) -> IResult<&str, Vec<(&str, &str)>> {
+fn key_values(input: &str) -> IResult<&str, Vec<(&str, String)>> {
     many0(key_value)(input)
 }
 
@@ -150,7 +491,7 @@ fn component(input: &str) -> IResult<&str, Token> {
         |(name, options)| {
             let options = options
                 .into_iter()
-                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .map(|(k, v)| (k.to_owned(), v))
                 .collect();
             Token::Component { name, options }
         },
@@ -161,8 +502,12 @@ fn tokens(input: &str) -> IResult<&str, Vec<Token>> {
     many1(alt((
         static_component,
         color,
+        background_color,
+        attribute,
         reset,
         conditional,
+        segment,
+        group,
         component,
     )))(input)
 }
@@ -186,7 +531,7 @@ mod tests {
     #[test]
     fn it_parses_a_component() {
         assert_eq!(
-            parse(&"{cwd}").unwrap(),
+            parse("{cwd}").unwrap(),
             vec![Token::Component {
                 name: Component::Cwd,
                 options: HashMap::new(),
@@ -197,7 +542,7 @@ mod tests {
     #[test]
     fn it_parses_a_component_and_static() {
         assert_eq!(
-            parse(&"{cwd} $").unwrap(),
+            parse("{cwd} $").unwrap(),
             vec![
                 Token::Component {
                     name: Component::Cwd,
@@ -213,7 +558,7 @@ mod tests {
         let mut options = HashMap::new();
         options.insert("style".to_string(), "default".to_string());
         assert_eq!(
-            parse(&"{cwd style=default}").unwrap(),
+            parse("{cwd style=default}").unwrap(),
             vec![Token::Component {
                 name: Component::Cwd,
                 options,
@@ -223,7 +568,7 @@ mod tests {
         let mut options = HashMap::new();
         options.insert("style".to_string(), "short".to_string());
         assert_eq!(
-            parse(&"{cwd style=short}").unwrap(),
+            parse("{cwd style=short}").unwrap(),
             vec![Token::Component {
                 name: Component::Cwd,
                 options,
@@ -233,7 +578,7 @@ mod tests {
         let mut options = HashMap::new();
         options.insert("style".to_string(), "long".to_string());
         assert_eq!(
-            parse(&"{cwd style=long}").unwrap(),
+            parse("{cwd style=long}").unwrap(),
             vec![Token::Component {
                 name: Component::Cwd,
                 options,
@@ -243,19 +588,19 @@ mod tests {
 
     #[test]
     fn it_parses_identifiers() {
-        assert_eq!(identifier(&"cwd").unwrap().1, "cwd".to_string());
+        assert_eq!(identifier("cwd").unwrap().1, "cwd".to_string());
         assert_eq!(
-            identifier(&"git_branch").unwrap().1,
+            identifier("git_branch").unwrap().1,
             "git_branch".to_string()
         );
 
-        assert!(identifier(&"end").is_err());
+        assert!(identifier("end").is_err());
     }
 
     #[test]
     fn it_parses_static() {
         assert_eq!(
-            parse(&"cwd").unwrap(),
+            parse("cwd").unwrap(),
             vec![Token::Static("cwd".to_string()),]
         );
     }
@@ -263,7 +608,7 @@ mod tests {
     #[test]
     fn it_allows_escaped_braces_as_static() {
         assert_eq!(
-            parse(&"{{cwd").unwrap(),
+            parse("{{cwd").unwrap(),
             vec![
                 Token::Static("{{".to_string()),
                 Token::Static("cwd".to_string()),
@@ -271,7 +616,7 @@ mod tests {
         );
 
         assert_eq!(
-            parse(&"{{cwd{cwd}").unwrap(),
+            parse("{{cwd{cwd}").unwrap(),
             vec![
                 Token::Static("{{".to_string()),
                 Token::Static("cwd".to_string()),
@@ -290,7 +635,7 @@ mod tests {
         options.insert("d".to_string(), "12".to_string());
 
         assert_eq!(
-            parse(&"{  git_branch a=bc   d=12  }  { git_commit }").unwrap(),
+            parse("{  git_branch a=bc   d=12  }  { git_commit }").unwrap(),
             vec![
                 Token::Component {
                     name: Component::GitBranch,
@@ -312,7 +657,7 @@ mod tests {
         options.insert("d".to_string(), "12".to_string());
 
         assert_eq!(
-            parse(&"{git_branch a=bc d=12}").unwrap(),
+            parse("{git_branch a=bc d=12}").unwrap(),
             vec![Token::Component {
                 name: Component::GitBranch,
                 options,
@@ -320,14 +665,49 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_parses_quoted_options() {
+        let mut options = HashMap::new();
+        options.insert("name".to_string(), "X".to_string());
+        options.insert("default".to_string(), "n/a }".to_string());
+        options.insert("format".to_string(), " | ".to_string());
+
+        assert_eq!(
+            parse(r#"{env name=X default="n/a }" format=' | '}"#).unwrap(),
+            vec![Token::Component {
+                name: Component::Env,
+                options,
+            }]
+        );
+    }
+
+    #[test]
+    fn it_parses_escapes_in_quoted_options() {
+        assert_eq!(
+            value(r#""a \"b\" \\ c""#).unwrap(),
+            ("", r#"a "b" \ c"#.to_string())
+        );
+        assert_eq!(
+            value(r#"'it\'s' rest"#).unwrap(),
+            (" rest", "it's".to_string())
+        );
+        assert_eq!(value(r#""""#).unwrap(), ("", "".to_string()));
+        assert_eq!(
+            value("unquoted rest").unwrap(),
+            (" rest", "unquoted".to_string())
+        );
+    }
+
     #[test]
     fn it_parses_conditionals() {
         assert_eq!(
-            parse(&"{if last_command_status}left{end}").unwrap(),
+            parse("{if last_command_status}left{end}").unwrap(),
             vec![Token::Conditional {
-                condition: Condition::LastCommandStatus,
-                left: vec![Token::Static("left".to_string())],
-                right: None,
+                branches: vec![(
+                    Condition::LastCommandStatus,
+                    vec![Token::Static("left".to_string())]
+                )],
+                else_branch: None,
             }]
         );
     }
@@ -335,29 +715,217 @@ mod tests {
     #[test]
     fn it_parses_conditionals_with_else_branch() {
         assert_eq!(
-            parse(&"{if last_command_status}left{else}right{end}").unwrap(),
+            parse("{if last_command_status}left{else}right{end}").unwrap(),
             vec![Token::Conditional {
-                condition: Condition::LastCommandStatus,
-                left: vec![Token::Static("left".to_string())],
-                right: Some(vec![Token::Static("right".to_string())]),
+                branches: vec![(
+                    Condition::LastCommandStatus,
+                    vec![Token::Static("left".to_string())]
+                )],
+                else_branch: Some(vec![Token::Static("right".to_string())]),
             }]
         );
     }
 
+    #[test]
+    fn it_parses_conditionals_with_elif_branches() {
+        let env = |name: &str| Condition::EnvironmentVariable(name.to_string());
+
+        assert_eq!(
+            parse("{if $A}a{elif $B}b{elif $C}c{else}d{end}").unwrap(),
+            vec![Token::Conditional {
+                branches: vec![
+                    (env("A"), vec![Token::Static("a".to_string())]),
+                    (env("B"), vec![Token::Static("b".to_string())]),
+                    (env("C"), vec![Token::Static("c".to_string())]),
+                ],
+                else_branch: Some(vec![Token::Static("d".to_string())]),
+            }]
+        );
+
+        assert!(parse("{if $A}a{else}b{elif $C}c{end}").is_err());
+        assert!(parse("{if $A}a{elif}b{end}").is_err());
+    }
+
+    #[test]
+    fn it_parses_segments() {
+        assert_eq!(
+            parse("{segment bg=blue fg=#000000} {cwd} {end}").unwrap(),
+            vec![Token::Segment {
+                background: Color::Blue,
+                foreground: Some(Color::Rgb { r: 0, g: 0, b: 0 }),
+                tokens: vec![
+                    Token::Static(" ".to_string()),
+                    Token::Component {
+                        name: Component::Cwd,
+                        options: HashMap::new(),
+                    },
+                    Token::Static(" ".to_string()),
+                ],
+            }]
+        );
+
+        assert!(parse("{segment fg=black}a{end}").is_err());
+        assert!(parse("{segment bg=nope}a{end}").is_err());
+        assert!(parse("{segment bg=blue foo=bar}a{end}").is_err());
+    }
+
+    #[test]
+    fn it_parses_groups() {
+        assert_eq!(
+            parse("{group}a{group}{git_branch}{end}{end}").unwrap(),
+            vec![Token::Group(vec![
+                Token::Static("a".to_string()),
+                Token::Group(vec![Token::Component {
+                    name: Component::GitBranch,
+                    options: HashMap::new(),
+                }]),
+            ])]
+        );
+
+        assert!(parse("{group}a").is_err());
+    }
+
+    #[test]
+    fn it_parses_boolean_conditions() {
+        let env = |name: &str| Box::new(Condition::EnvironmentVariable(name.to_string()));
+
+        assert_eq!(
+            if_condition(" $SSH_CONNECTION or $SUDO_USER}").unwrap().1,
+            Condition::Or(env("SSH_CONNECTION"), env("SUDO_USER"))
+        );
+        assert_eq!(
+            if_condition(" not last_command_status}").unwrap().1,
+            Condition::Not(Box::new(Condition::LastCommandStatus))
+        );
+        assert_eq!(
+            if_condition(" $A or $B and not $C}").unwrap().1,
+            Condition::Or(
+                env("A"),
+                Box::new(Condition::And(env("B"), Box::new(Condition::Not(env("C")))))
+            )
+        );
+        assert_eq!(
+            if_condition(" ( $A or $B ) and not($C)}").unwrap().1,
+            Condition::And(
+                Box::new(Condition::Or(env("A"), env("B"))),
+                Box::new(Condition::Not(env("C")))
+            )
+        );
+    }
+
+    #[test]
+    fn it_parses_git_conditions() {
+        assert_eq!(
+            if_condition(" in_git_repo and not git_dirty}").unwrap().1,
+            Condition::And(
+                Box::new(Condition::InGitRepo),
+                Box::new(Condition::Not(Box::new(Condition::GitDirty)))
+            )
+        );
+        assert_eq!(
+            if_condition(" git_detached or git_bare}").unwrap().1,
+            Condition::Or(
+                Box::new(Condition::GitDetached),
+                Box::new(Condition::GitBare)
+            )
+        );
+    }
+
+    #[test]
+    fn it_parses_path_conditions() {
+        assert_eq!(
+            if_condition(r#" cwd_matches "~/work/**"}"#).unwrap().1,
+            Condition::CwdMatches("~/work/**".to_string())
+        );
+        assert_eq!(
+            if_condition(r#" file_exists "Cargo.toml"}"#).unwrap().1,
+            Condition::FileExists {
+                path: "Cargo.toml".to_string(),
+                upward: false
+            }
+        );
+        assert_eq!(
+            if_condition(r#" file_exists "Cargo.toml" upward=true}"#)
+                .unwrap()
+                .1,
+            Condition::FileExists {
+                path: "Cargo.toml".to_string(),
+                upward: true
+            }
+        );
+
+        assert!(parse("{if cwd_matches}a{end}").is_err());
+        assert!(parse(r#"{if file_exists "a" upward=yes}a{end}"#).is_err());
+    }
+
+    #[test]
+    fn it_parses_comparison_conditions() {
+        assert_eq!(
+            if_condition(r#" $TERM_PROGRAM == "vscode"}"#).unwrap().1,
+            Condition::EnvironmentVariableComparison(
+                "TERM_PROGRAM".to_string(),
+                StringComparison::Equal,
+                "vscode".to_string()
+            )
+        );
+        assert_eq!(
+            if_condition(r#" $AWS_PROFILE=~"^prod" and $A != "a \"b\""}"#)
+                .unwrap()
+                .1,
+            Condition::And(
+                Box::new(Condition::EnvironmentVariableComparison(
+                    "AWS_PROFILE".to_string(),
+                    StringComparison::Matches,
+                    "^prod".to_string()
+                )),
+                Box::new(Condition::EnvironmentVariableComparison(
+                    "A".to_string(),
+                    StringComparison::NotEqual,
+                    "a \"b\"".to_string()
+                ))
+            )
+        );
+        assert_eq!(
+            if_condition(" status == 130}").unwrap().1,
+            Condition::Status(NumericComparison::Equal, 130)
+        );
+        assert_eq!(
+            if_condition(" status >= 1}").unwrap().1,
+            Condition::Status(NumericComparison::GreaterOrEqual, 1)
+        );
+        assert_eq!(
+            if_condition(" status<2}").unwrap().1,
+            Condition::Status(NumericComparison::Less, 2)
+        );
+
+        assert!(parse("{if status =~ 1}a{end}").is_err());
+        assert!(parse("{if $A == vscode}a{end}").is_err());
+        assert!(parse("{if $A =~ \"(\"}a{end}").is_err());
+    }
+
+    #[test]
+    fn it_reports_invalid_conditions() {
+        let error = parse("{if ($A}a{end}").unwrap_err().to_string();
+        assert!(error.contains("in condition"), "{}", error);
+
+        assert!(parse("{if $A and}a{end}").is_err());
+        assert!(parse("{if not}a{end}").is_err());
+    }
+
     #[test]
     fn it_ensures_all_input_is_consumed() {
-        assert!(parse(&"foo{git_branch bar=").is_err());
+        assert!(parse("foo{git_branch bar=").is_err());
     }
 
     #[test]
     fn it_parses_style_components() {
-        assert_eq!(parse(&"{green}").unwrap(), vec![Token::Color(Color::Green)]);
+        assert_eq!(parse("{green}").unwrap(), vec![Token::Color(Color::Green)]);
     }
 
     #[test]
     fn it_parses_style_with_whitespace() {
         assert_eq!(
-            parse(&"{  green  }").unwrap(),
+            parse("{  green  }").unwrap(),
             vec![Token::Color(Color::Green)]
         );
     }
@@ -366,18 +934,87 @@ mod tests {
     fn it_parses_environment_variable_names() {
         let subject = environment_variable_name;
 
-        assert_eq!(subject(&"$TEST").unwrap(), ("", "TEST"));
-        assert_eq!(subject(&"$FOO_BAR").unwrap(), ("", "FOO_BAR"));
-        assert_eq!(subject(&"$FOO BAR").unwrap(), (" BAR", "FOO"));
+        assert_eq!(subject("$TEST").unwrap(), ("", "TEST"));
+        assert_eq!(subject("$FOO_BAR").unwrap(), ("", "FOO_BAR"));
+        assert_eq!(subject("$FOO BAR").unwrap(), (" BAR", "FOO"));
+        assert_eq!(subject("$http_proxy").unwrap(), ("", "http_proxy"));
+        assert_eq!(subject("$VAR2").unwrap(), ("", "VAR2"));
+        assert_eq!(subject("$__git_prompt").unwrap(), ("", "__git_prompt"));
+
+        assert!(subject("$2VAR").is_err());
     }
 
     #[test]
     fn it_parses_colors() {
         let a = assert_result_return_tokens;
 
-        assert_eq!(a(color(&"{grey}")), Token::Color(Color::Grey));
-        assert_eq!(a(color(&"{dArk_grEy}")), Token::Color(Color::DarkGrey));
-        assert_eq!(a(color(&"{White}")), Token::Color(Color::White));
+        assert_eq!(a(color("{grey}")), Token::Color(Color::Grey));
+        assert_eq!(a(color("{dArk_grEy}")), Token::Color(Color::DarkGrey));
+        assert_eq!(a(color("{White}")), Token::Color(Color::White));
+    }
+
+    #[test]
+    fn it_parses_background_colors_and_attributes() {
+        assert_eq!(
+            parse("{bg_blue}{bold}{ italic }{dim}{underline}{reverse}").unwrap(),
+            vec![
+                Token::BackgroundColor(Color::Blue),
+                Token::Attribute(Attribute::Bold),
+                Token::Attribute(Attribute::Italic),
+                Token::Attribute(Attribute::Dim),
+                Token::Attribute(Attribute::Underlined),
+                Token::Attribute(Attribute::Reverse),
+            ]
+        );
+        assert!(parse("{bg_nope}").is_err());
+    }
+
+    #[test]
+    fn it_parses_hex_colors() {
+        let a = assert_result_return_tokens;
+
+        assert_eq!(
+            a(color("{#ff8800}")),
+            Token::Color(Color::Rgb {
+                r: 255,
+                g: 136,
+                b: 0
+            })
+        );
+        assert_eq!(
+            a(color("{#FF8800}")),
+            Token::Color(Color::Rgb {
+                r: 255,
+                g: 136,
+                b: 0
+            })
+        );
+        assert!(color("{#ff88}").is_err());
+        assert!(color("{#ff88zz}").is_err());
+    }
+
+    #[test]
+    fn it_parses_rgb_colors() {
+        let a = assert_result_return_tokens;
+
+        assert_eq!(
+            a(color("{rgb 255 136 0}")),
+            Token::Color(Color::Rgb {
+                r: 255,
+                g: 136,
+                b: 0
+            })
+        );
+        assert!(color("{rgb 256 136 0}").is_err());
+        assert!(color("{rgb 255 136}").is_err());
+    }
+
+    #[test]
+    fn it_parses_ansi_colors() {
+        let a = assert_result_return_tokens;
+
+        assert_eq!(a(color("{color=208}")), Token::Color(Color::AnsiValue(208)));
+        assert!(color("{color=256}").is_err());
     }
 
     fn assert_result_return_tokens<O: std::fmt::Debug, E: std::fmt::Debug>(
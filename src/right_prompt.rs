@@ -0,0 +1,131 @@
+//! Joins the left and right prompts for each shell.
+
+use crossterm::cursor::{MoveToColumn, RestorePosition, SavePosition};
+
+use crate::Shell;
+
+// Zsh has native support for a right prompt via RPROMPT. The init script splits the output on the
+// null byte and assigns the second half to RPROMPT.
+//
+// Bash has no such support so the right prompt is emulated by saving the cursor position, drawing
+// the right prompt at the end of the last line of the left prompt, and then restoring the cursor.
+pub fn join(shell: &Shell, left: String, right: String, columns: usize) -> String {
+    match shell {
+        Shell::Zsh => format!("{}\0{}", left, right),
+        Shell::Bash => {
+            let (right, width) = strip_non_printing_markers(&right);
+            let right = format!(
+                "\\[{}{}{}{}\\]",
+                SavePosition,
+                MoveToColumn((columns.saturating_sub(width) + 1) as u16),
+                right,
+                RestorePosition
+            );
+
+            // The right prompt belongs on the same line as the cursor
+            match left.rfind('\n') {
+                Some(i) => format!("{}{}{}", &left[..=i], right, &left[i + 1..]),
+                None => format!("{}{}", right, left),
+            }
+        }
+        Shell::Fish => left,
+    }
+}
+
+// Bash's non-printing markers (\[ and \]) can't be nested, so they need to be removed from the
+// right prompt before it's wrapped as a whole. Returns the remaining prompt and the number of
+// columns it will take up once Bash has expanded it.
+fn strip_non_printing_markers(prompt: &str) -> (String, usize) {
+    let mut output = String::with_capacity(prompt.len());
+    let mut printable = Vec::new();
+    let mut non_printing = false;
+
+    let mut chars = prompt.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            if !non_printing {
+                printable.push(c);
+            }
+            continue;
+        }
+
+        match chars.next() {
+            Some('[') => non_printing = true,
+            Some(']') => non_printing = false,
+            // Other escapes (e.g. "\\" or "\u") are decoded by Bash, this assumes they will
+            // expand to a single character.
+            Some(escaped) => {
+                output.push(c);
+                output.push(escaped);
+                if !non_printing {
+                    printable.push(escaped);
+                }
+            }
+            None => output.push(c),
+        }
+    }
+
+    (output, expanded_width(&printable))
+}
+
+// After decoding its escapes Bash expands the prompt as if it was in double quotes, removing the
+// backslashes added by `Shell::escape`.
+fn expanded_width(decoded: &[char]) -> usize {
+    let mut width = 0;
+    let mut chars = decoded.iter().peekable();
+
+    while let Some(c) = chars.next() {
+        if *c == '\\' {
+            if let Some('$') | Some('`') | Some('\\') = chars.peek() {
+                chars.next();
+            }
+        }
+        width += 1;
+    }
+
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_joins_zsh_with_a_null_byte() {
+        assert_eq!(
+            join(&Shell::Zsh, "left".to_string(), "right".to_string(), 80),
+            "left\0right"
+        );
+    }
+
+    #[test]
+    fn it_pads_bash() {
+        assert_eq!(
+            join(&Shell::Bash, "$ ".to_string(), "right".to_string(), 80),
+            "\\[\u{1b}7\u{1b}[76Gright\u{1b}8\\]$ "
+        );
+    }
+
+    #[test]
+    fn it_puts_the_bash_right_prompt_on_the_last_line() {
+        assert_eq!(
+            join(&Shell::Bash, "a\n$ ".to_string(), "b".to_string(), 10),
+            "a\n\\[\u{1b}7\u{1b}[10Gb\u{1b}8\\]$ "
+        );
+    }
+
+    #[test]
+    fn it_strips_non_printing_markers() {
+        assert_eq!(
+            strip_non_printing_markers("\\[\u{1b}[38;5;10m\\]master\\[\u{1b}[0m\\]"),
+            ("\u{1b}[38;5;10mmaster\u{1b}[0m".to_string(), 6)
+        );
+    }
+
+    #[test]
+    fn it_measures_escaped_values() {
+        let escaped = Shell::Bash.escape("$(a)\\`b`");
+        assert_eq!(strip_non_printing_markers(&escaped).1, 8);
+    }
+}
@@ -1,7 +1,8 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Shell {
     Zsh,
     Bash,
+    Fish,
 }
 
 impl std::str::FromStr for Shell {
@@ -11,7 +12,66 @@ impl std::str::FromStr for Shell {
         match input {
             "zsh" => Ok(Shell::Zsh),
             "bash" => Ok(Shell::Bash),
-            _ => Err("valid options are: bash, zsh\n"),
+            "fish" => Ok(Shell::Fish),
+            _ => Err("valid options are: bash, fish, zsh\n"),
         }
     }
 }
+
+impl Shell {
+    // Escapes characters that would otherwise be interpreted by the shell when the prompt is
+    // expanded. Zsh uses "prompt_subst" and Bash uses "promptvars" so both would perform parameter
+    // expansion and command substitution on the prompt.
+    pub fn escape(&self, input: &str) -> String {
+        let mut output = String::with_capacity(input.len());
+
+        for c in input.chars() {
+            match (self, c) {
+                (Shell::Zsh, '%') => output.push_str("%%"),
+                (Shell::Zsh, '$') | (Shell::Zsh, '`') | (Shell::Zsh, '\\') => {
+                    output.push('\\');
+                    output.push(c);
+                }
+                // Bash decodes backslash escapes before expansion, so the escaping backslash
+                // needs to be escaped itself.
+                (Shell::Bash, '$') | (Shell::Bash, '`') => {
+                    output.push_str("\\\\");
+                    output.push(c);
+                }
+                (Shell::Bash, '\\') => output.push_str("\\\\\\\\"),
+                _ => output.push(c),
+            }
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_escapes_zsh() {
+        assert_eq!(Shell::Zsh.escape("100%"), "100%%");
+        assert_eq!(Shell::Zsh.escape("$(rm -rf ~)"), "\\$(rm -rf ~)");
+        assert_eq!(Shell::Zsh.escape("`pwd`"), "\\`pwd\\`");
+        assert_eq!(Shell::Zsh.escape("a\\b"), "a\\\\b");
+    }
+
+    #[test]
+    fn it_escapes_bash() {
+        assert_eq!(Shell::Bash.escape("100%"), "100%");
+        assert_eq!(Shell::Bash.escape("$(rm -rf ~)"), "\\\\$(rm -rf ~)");
+        assert_eq!(Shell::Bash.escape("`pwd`"), "\\\\`pwd\\\\`");
+        assert_eq!(Shell::Bash.escape("a\\b"), "a\\\\\\\\b");
+    }
+
+    #[test]
+    fn it_does_not_escape_fish() {
+        assert_eq!(
+            Shell::Fish.escape("$(rm -rf ~) 100% `pwd`"),
+            "$(rm -rf ~) 100% `pwd`"
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crossterm::style::{Attribute, Color, ResetColor, SetForegroundColor};
+use crossterm::style::{Attribute, Color, ResetColor, SetBackgroundColor, SetForegroundColor};
 
 use crate::Shell;
 
@@ -6,6 +6,8 @@ use std::fmt;
 
 pub enum Style<'a> {
     Color(&'a Shell, Color),
+    BackgroundColor(&'a Shell, Color),
+    Attribute(&'a Shell, Attribute),
     Reset(&'a Shell),
     Underlined(&'a Shell),
     NoUnderline(&'a Shell),
@@ -13,20 +15,20 @@ pub enum Style<'a> {
 
 impl<'a> Style<'a> {
     pub fn from_color_token(color: &Color, shell: &'a Shell) -> Self {
-        match color {
-            Color::Reset | Color::Rgb { .. } => panic!("unsupported color"),
-            _ => Style::Color(shell, *color),
-        }
+        Style::Color(shell, *color)
     }
 }
 
 impl fmt::Display for Style<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Style::Color(shell, color) => write(f, *shell, SetForegroundColor(*color)),
-            Style::Reset(shell) => write(f, *shell, ResetColor),
-            Style::Underlined(shell) => write(f, *shell, Attribute::Underlined),
-            Style::NoUnderline(shell) => write(f, *shell, Attribute::NoUnderline),
+            Style::Color(shell, color) => write(f, shell, SetForegroundColor(*color)),
+            Style::BackgroundColor(shell, color) => write(f, shell, SetBackgroundColor(*color)),
+            Style::Attribute(shell, attribute) => write(f, shell, attribute),
+            // Resets colors and attributes
+            Style::Reset(shell) => write(f, shell, ResetColor),
+            Style::Underlined(shell) => write(f, shell, Attribute::Underlined),
+            Style::NoUnderline(shell) => write(f, shell, Attribute::NoUnderline),
         }
     }
 }
@@ -42,6 +44,8 @@ where
         Shell::Zsh => write!(f, "%{{{}%}}", style),
         // /[.../]
         Shell::Bash => write!(f, "\\[{}\\]", style),
+        // Fish calculates the prompt width itself, no wrapping required
+        Shell::Fish => write!(f, "{}", style),
     }
 }
 
@@ -53,6 +57,36 @@ mod tests {
     fn it_wraps_green() {
         assert_green(Shell::Zsh, "%{\u{1b}[38;5;10m%}");
         assert_green(Shell::Bash, "\\[\u{1b}[38;5;10m\\]");
+        assert_green(Shell::Fish, "\u{1b}[38;5;10m");
+    }
+
+    #[test]
+    fn it_wraps_rgb_and_ansi_values() {
+        let rgb = Color::Rgb {
+            r: 255,
+            g: 136,
+            b: 0,
+        };
+        assert_eq!(
+            Style::from_color_token(&rgb, &Shell::Zsh).to_string(),
+            "%{\u{1b}[38;2;255;136;0m%}"
+        );
+        assert_eq!(
+            Style::from_color_token(&Color::AnsiValue(208), &Shell::Bash).to_string(),
+            "\\[\u{1b}[38;5;208m\\]"
+        );
+    }
+
+    #[test]
+    fn it_wraps_background_colors_and_attributes() {
+        assert_eq!(
+            Style::BackgroundColor(&Shell::Zsh, Color::Blue).to_string(),
+            "%{\u{1b}[48;5;12m%}"
+        );
+        assert_eq!(
+            Style::Attribute(&Shell::Bash, Attribute::Bold).to_string(),
+            "\\[\u{1b}[1m\\]"
+        );
     }
 
     fn assert_green(shell: Shell, expected: &str) {
@@ -2,13 +2,51 @@
 
 use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::fmt;
 
-use crossterm::style::Color;
+use crossterm::style::{Attribute, Color};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StringComparison {
+    // ==
+    Equal,
+    // !=
+    NotEqual,
+    // =~
+    Matches,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NumericComparison {
+    // ==
+    Equal,
+    // !=
+    NotEqual,
+    // <
+    Less,
+    // <=
+    LessOrEqual,
+    // >
+    Greater,
+    // >=
+    GreaterOrEqual,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum Condition {
     LastCommandStatus,
+    InGitRepo,
+    GitDirty,
+    GitDetached,
+    GitBare,
     EnvironmentVariable(String),
+    EnvironmentVariableComparison(String, StringComparison, String),
+    Status(NumericComparison, usize),
+    CwdMatches(String),
+    FileExists { path: String, upward: bool },
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
 }
 
 impl TryFrom<&str> for Condition {
@@ -17,18 +55,41 @@ impl TryFrom<&str> for Condition {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
             "last_command_status" => Ok(Condition::LastCommandStatus),
+            "in_git_repo" => Ok(Condition::InGitRepo),
+            "git_dirty" => Ok(Condition::GitDirty),
+            "git_detached" => Ok(Condition::GitDetached),
+            "git_bare" => Ok(Condition::GitBare),
             _ => Err(()),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
+impl Condition {
+    pub fn is_git(&self) -> bool {
+        match self {
+            Condition::InGitRepo
+            | Condition::GitDirty
+            | Condition::GitDetached
+            | Condition::GitBare => true,
+            Condition::Not(condition) => condition.is_git(),
+            Condition::And(left, right) | Condition::Or(left, right) => {
+                left.is_git() || right.is_git()
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Component {
+    CmdDuration,
     Cwd,
     Env,
+    GitAheadBehind,
     GitBranch,
     GitCommit,
     GitStash,
+    GitState,
     GitStatus,
     Hostname,
     Jobs,
@@ -40,11 +101,14 @@ impl TryFrom<&str> for Component {
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         match value {
+            "cmd_duration" => Ok(Component::CmdDuration),
             "cwd" => Ok(Component::Cwd),
             "env" => Ok(Component::Env),
+            "git_ahead_behind" => Ok(Component::GitAheadBehind),
             "git_branch" => Ok(Component::GitBranch),
             "git_commit" => Ok(Component::GitCommit),
             "git_stash" => Ok(Component::GitStash),
+            "git_state" => Ok(Component::GitState),
             "git_status" => Ok(Component::GitStatus),
             "hostname" => Ok(Component::Hostname),
             "jobs" => Ok(Component::Jobs),
@@ -54,6 +118,40 @@ impl TryFrom<&str> for Component {
     }
 }
 
+impl Component {
+    pub fn is_git(&self) -> bool {
+        matches!(
+            self,
+            Component::GitAheadBehind
+                | Component::GitBranch
+                | Component::GitCommit
+                | Component::GitStash
+                | Component::GitState
+                | Component::GitStatus
+        )
+    }
+}
+
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Component::CmdDuration => "cmd_duration",
+            Component::Cwd => "cwd",
+            Component::Env => "env",
+            Component::GitAheadBehind => "git_ahead_behind",
+            Component::GitBranch => "git_branch",
+            Component::GitCommit => "git_commit",
+            Component::GitStash => "git_stash",
+            Component::GitState => "git_state",
+            Component::GitStatus => "git_status",
+            Component::Hostname => "hostname",
+            Component::Jobs => "jobs",
+            Component::User => "user",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Token {
     Component {
@@ -62,10 +160,19 @@ pub enum Token {
     },
     Static(String),
     Color(Color),
+    BackgroundColor(Color),
+    Attribute(Attribute),
     Reset,
+    Segment {
+        background: Color,
+        foreground: Option<Color>,
+        tokens: Vec<Token>,
+    },
+    // Contents are squashed together, independently of any colors
+    Group(Vec<Token>),
     Conditional {
-        condition: Condition,
-        left: Vec<Token>,
-        right: Option<Vec<Token>>,
+        // The {if} branch followed by any {elif} branches
+        branches: Vec<(Condition, Vec<Token>)>,
+        else_branch: Option<Vec<Token>>,
     },
 }